@@ -0,0 +1,111 @@
+use std::{path::PathBuf, str::FromStr};
+
+use anyhow::{anyhow, bail};
+use bitcoin::{consensus::encode, Amount, FeeRate, TxOut};
+use ordinals::{Edict, RuneId};
+
+use crate::{
+    batch::{print_batch_table, read_rune_batch},
+    constant::POSTAGE,
+    rune::build_edict_script_buf,
+    send::{build_psbt_with_inputs, gather_rune_inputs},
+    setting::Settings,
+    snipe,
+    utils::{print_table, select_confirm},
+};
+
+/// Standard OP_RETURN limit
+const MAX_OP_RETURN_SIZE: usize = 83;
+
+/// Distribute one rune to every recipient of the batch file in one tx
+pub fn airdrop(
+    settings: Settings,
+    name: &str,
+    batch: PathBuf,
+    fee_rate: u64,
+    broadcast: bool,
+) -> anyhow::Result<()> {
+    let wallet = settings.wallet()?;
+    let ordi_addr = wallet.ordi_addr();
+    let ordi_api = settings.ordi_api();
+    let fee_rate = FeeRate::from_sat_per_vb(fee_rate).ok_or(anyhow!("fee_rate is invalid"))?;
+
+    let rune = ordi_api.fetch_rune_id(name)?;
+    let rune_id = RuneId::from_str(&rune.id)?;
+    let recipients = read_rune_batch(&batch, settings.network, rune.entry.divisibility)?;
+    print_batch_table(&recipients);
+
+    let total = recipients.iter().map(|(_, amount)| *amount).sum::<u128>();
+    let (inputs, balance, only_rune) =
+        gather_rune_inputs(&settings, &ordi_addr, &rune.entry.spaced_rune, total)?;
+
+    // rune change at output 0 unless nothing is left
+    let change = balance > total || !only_rune;
+    let offset = change as u32;
+
+    let mut outputs = Vec::new();
+    if change {
+        outputs.push(TxOut {
+            value: POSTAGE,
+            script_pubkey: ordi_addr.script_pubkey(),
+        });
+    }
+    let mut edicts = Vec::new();
+    for (i, (addr, amount)) in recipients.into_iter().enumerate() {
+        outputs.push(TxOut {
+            value: POSTAGE,
+            script_pubkey: addr.script_pubkey(),
+        });
+        edicts.push(Edict {
+            id: rune_id,
+            amount,
+            output: i as u32 + offset,
+        });
+    }
+    let runestone = build_edict_script_buf(edicts, change.then_some(0));
+    if runestone.len() > MAX_OP_RETURN_SIZE {
+        bail!(
+            "Runestone is {} bytes, more than {}, please split the batch",
+            runestone.len(),
+            MAX_OP_RETURN_SIZE
+        );
+    }
+    outputs.push(TxOut {
+        value: Amount::ZERO,
+        script_pubkey: runestone,
+    });
+
+    let (utxos, _) = snipe::get_wallet_utxos(&settings, wallet.as_ref())?;
+    let mut psbt = build_psbt_with_inputs(
+        wallet.change_addr(),
+        inputs,
+        outputs,
+        fee_rate,
+        utxos,
+        &settings.coin_selection,
+    )?;
+
+    let ok = wallet.sign_all(&mut psbt)?;
+    if !ok {
+        bail!("Sign failed")
+    }
+    let fee = psbt.fee()?;
+    let signed_tx = psbt.clone().extract_tx()?;
+    let hex = encode::serialize_hex(&signed_tx);
+
+    log::info!(
+        "[{}] FeeRate: {:.1} sat/vb , TotalFee: {} sat , Size: {} vb",
+        signed_tx.txid(),
+        fee.to_sat() as f64 / signed_tx.vsize() as f64,
+        fee.to_sat(),
+        signed_tx.vsize()
+    );
+    print_table(&psbt, settings.network);
+
+    if broadcast {
+        if select_confirm("Please confirm") {
+            settings.broadcast(&hex)?;
+        }
+    }
+    Ok(())
+}
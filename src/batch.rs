@@ -0,0 +1,186 @@
+use std::{collections::HashSet, fmt::Display, fs, iter::Sum, path::Path, str::FromStr};
+
+use anyhow::{anyhow, bail};
+use bitcoin::{Address, Amount, Denomination, Network};
+use serde::Deserialize;
+
+use crate::rune::parse_rune_amount;
+
+#[derive(Deserialize)]
+struct Row {
+    address: String,
+    amount: serde_json::Value,
+}
+
+/// Recipients from a `.json` file (`[{"address": "bc1..", "amount": 0.001}]`),
+/// otherwise csv (`address,amount` per line, btc).
+pub(crate) fn read_batch(path: &Path, network: Network) -> anyhow::Result<Vec<(Address, Amount)>> {
+    let rows = read_rows(path)?;
+    validate(rows, network, |addr, amount| {
+        let amount = Amount::from_str_in(amount, Denomination::Bitcoin)?;
+        let dust = addr.script_pubkey().dust_value();
+        if amount < dust {
+            bail!("amount {} is below dust {}", amount, dust);
+        }
+        Ok(amount)
+    })
+}
+
+/// Same file format as [`read_batch`], amounts are rune amounts with decimals.
+pub(crate) fn read_rune_batch(
+    path: &Path,
+    network: Network,
+    divisibility: u32,
+) -> anyhow::Result<Vec<(Address, u128)>> {
+    let rows = read_rows(path)?;
+    validate(rows, network, |_, amount| {
+        parse_rune_amount(amount, divisibility)
+    })
+}
+
+/// (row, address, amount)
+fn read_rows(path: &Path) -> anyhow::Result<Vec<(usize, String, String)>> {
+    let content = fs::read_to_string(path)?;
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("json") => parse_json(&content),
+        _ => parse_csv(&content),
+    }
+}
+
+fn parse_csv(content: &str) -> anyhow::Result<Vec<(usize, String, String)>> {
+    let mut rows = Vec::new();
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (address, amount) = line
+            .split_once(',')
+            .ok_or(anyhow!("Line {}: expect `address,amount`", i + 1))?;
+        let (address, amount) = (address.trim(), amount.trim());
+        // header
+        if rows.is_empty() && address.eq_ignore_ascii_case("address") {
+            continue;
+        }
+        rows.push((i + 1, address.to_string(), amount.to_string()));
+    }
+    Ok(rows)
+}
+
+fn parse_json(content: &str) -> anyhow::Result<Vec<(usize, String, String)>> {
+    serde_json::from_str::<Vec<Row>>(content)?
+        .into_iter()
+        .enumerate()
+        .map(|(i, row)| {
+            let amount = match row.amount {
+                serde_json::Value::String(amount) => amount,
+                serde_json::Value::Number(amount) => amount.to_string(),
+                _ => bail!("Row {}: amount must be a number or string", i + 1),
+            };
+            Ok((i + 1, row.address, amount))
+        })
+        .collect()
+}
+
+fn validate<T>(
+    rows: Vec<(usize, String, String)>,
+    network: Network,
+    parse_amount: impl Fn(&Address, &str) -> anyhow::Result<T>,
+) -> anyhow::Result<Vec<(Address, T)>> {
+    if rows.is_empty() {
+        bail!("No recipient in batch");
+    }
+    let mut seen = HashSet::new();
+    let mut recipients = Vec::new();
+    for (row, address, amount) in rows {
+        let addr = Address::from_str(&address)
+            .map_err(|e| anyhow!("Row {}: invalid address {}: {}", row, address, e))?
+            .require_network(network)
+            .map_err(|_| anyhow!("Row {}: {} is not a {} address", row, address, network))?;
+        if !seen.insert(addr.clone()) {
+            bail!("Row {}: duplicate address {}", row, address);
+        }
+        let amount = parse_amount(&addr, &amount)
+            .map_err(|e| anyhow!("Row {}: invalid amount {}: {}", row, amount, e))?;
+        recipients.push((addr, amount));
+    }
+    Ok(recipients)
+}
+
+pub(crate) fn print_batch_table<T: Display + Copy + Sum>(recipients: &[(Address, T)]) {
+    let mut table = comfy_table::Table::new();
+    table.set_header(vec!["#", "Address", "Amount"]);
+    for (i, (addr, amount)) in recipients.iter().enumerate() {
+        table.add_row(vec![
+            (i + 1).to_string(),
+            addr.to_string(),
+            amount.to_string(),
+        ]);
+    }
+    table.add_row(vec![
+        "Total".to_string(),
+        format!("{} recipients", recipients.len()),
+        recipients
+            .iter()
+            .map(|(_, amount)| *amount)
+            .sum::<T>()
+            .to_string(),
+    ]);
+    println!("{}", table);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADDR1: &str = "bc1pyf5f0r5eqxer5rdrwm98grgz5tem6k8xgtnm49he2m4kjhacrsms6p6888";
+    const ADDR2: &str = "bc1p3kekh5h8cawt70uyq0k6zn2lv8774sqvff0zz7dttg48xnh4h3yqnuxhfj";
+
+    #[test]
+    fn test_parse_csv() {
+        let content = format!(
+            "address,amount\n{},0.0001\n\n# comment\n{}, 0.5\n",
+            ADDR1, ADDR2
+        );
+        let rows = parse_csv(&content).unwrap();
+        assert_eq!(rows[0].0, 2);
+        assert_eq!(rows[1], (5, ADDR2.to_string(), "0.5".to_string()));
+    }
+
+    #[test]
+    fn test_parse_json() {
+        let content = format!(
+            r#"[{{"address":"{}","amount":0.0001}},{{"address":"{}","amount":"0.0002"}}]"#,
+            ADDR1, ADDR2
+        );
+        let rows = parse_json(&content).unwrap();
+        assert_eq!(rows[0], (1, ADDR1.to_string(), "0.0001".to_string()));
+        assert_eq!(rows[1], (2, ADDR2.to_string(), "0.0002".to_string()));
+    }
+
+    #[test]
+    fn test_read_batch() {
+        let path = std::env::temp_dir().join("btct_test_read_batch.csv");
+        let read = |content: String| {
+            fs::write(&path, content).unwrap();
+            read_batch(&path, Network::Bitcoin)
+        };
+        let recipients = read(format!("{},0.0001\n{},0.5", ADDR1, ADDR2)).unwrap();
+        assert_eq!(recipients[0].1, Amount::from_sat(10_000));
+        assert_eq!(recipients[1].1, Amount::from_sat(50_000_000));
+        // duplicate
+        assert!(read(format!("{},0.0001\n{},0.0002", ADDR1, ADDR1)).is_err());
+        // dust
+        assert!(read(format!("{},0.000001", ADDR1)).is_err());
+
+        fs::write(&path, format!("{},0.0001", ADDR1)).unwrap();
+        assert!(read_batch(&path, Network::Testnet).is_err());
+
+        fs::write(&path, format!("{},1.5\n{},2", ADDR1, ADDR2)).unwrap();
+        let recipients = read_rune_batch(&path, Network::Bitcoin, 2).unwrap();
+        assert_eq!(recipients[0].1, 150);
+        assert_eq!(recipients[1].1, 200);
+        assert!(read_rune_batch(&path, Network::Bitcoin, 0).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
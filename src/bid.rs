@@ -0,0 +1,103 @@
+use clap::ValueEnum;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, ValueEnum)]
+pub enum BidStep {
+    /// Origin fee rate + increase_rate sat/vb
+    #[default]
+    Linear,
+    /// Origin fee rate + increase_rate percent
+    Multiplicative,
+}
+
+/// Caps of a bidding war, every round outbids the tx that replaced ours
+#[derive(Debug, Clone)]
+pub struct BidPolicy {
+    pub step: BidStep,
+    pub increase_rate: u64,
+    pub max_rounds: u32,
+    /// Sat, total fee of one replacement
+    pub max_fee: Option<u64>,
+    /// Sat/vb
+    pub max_fee_rate: Option<u64>,
+}
+
+impl BidPolicy {
+    /// Single round, no cap
+    pub fn once(increase_rate: u64) -> Self {
+        Self {
+            step: BidStep::Linear,
+            increase_rate,
+            max_rounds: 1,
+            max_fee: None,
+            max_fee_rate: None,
+        }
+    }
+
+    /// Fee rate to outbid `origin_rate`, None over the cap
+    pub(crate) fn next_fee_rate(&self, origin_rate: u64) -> Option<u64> {
+        let fee_rate = match self.step {
+            BidStep::Linear => origin_rate + self.increase_rate,
+            BidStep::Multiplicative => {
+                origin_rate + (origin_rate * self.increase_rate).div_ceil(100).max(1)
+            }
+        };
+        if self.max_fee_rate.is_some_and(|e| fee_rate > e) {
+            return None;
+        }
+        Some(fee_rate)
+    }
+}
+
+/// One replacement we broadcast
+pub(crate) struct Round {
+    pub(crate) txid: String,
+    /// The tx we outbid
+    pub(crate) replaced: String,
+    pub(crate) fee: u64,
+    pub(crate) fee_rate: f64,
+    pub(crate) confirmed: bool,
+    pub(crate) outbid_by: Option<String>,
+}
+
+pub(crate) fn print_bid_report(rounds: &[Round]) {
+    let mut table = comfy_table::Table::new();
+    table.set_header(vec![
+        "Round", "Txid", "Replaced", "FeeRate", "Fee", "Result",
+    ]);
+    for (i, round) in rounds.iter().enumerate() {
+        let result = match (&round.outbid_by, round.confirmed) {
+            (_, true) => "confirmed".to_string(),
+            (Some(txid), _) => format!("outbid by {}", txid),
+            (None, _) => "-".to_string(),
+        };
+        table.add_row(vec![
+            (i + 1).to_string(),
+            round.txid.clone(),
+            round.replaced.clone(),
+            format!("{:.1} sat/vb", round.fee_rate),
+            format!("{} sat", round.fee),
+            result,
+        ]);
+    }
+    println!("{}", table);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_fee_rate() {
+        let mut bid = BidPolicy::once(5);
+        assert_eq!(bid.next_fee_rate(20), Some(25));
+        bid.max_fee_rate = Some(25);
+        assert_eq!(bid.next_fee_rate(21), None);
+
+        bid.step = BidStep::Multiplicative;
+        bid.increase_rate = 10;
+        bid.max_fee_rate = None;
+        assert_eq!(bid.next_fee_rate(100), Some(110));
+        assert_eq!(bid.next_fee_rate(15), Some(17));
+        assert_eq!(bid.next_fee_rate(1), Some(2));
+    }
+}
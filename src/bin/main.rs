@@ -6,13 +6,26 @@ use bitcoin::{
     Transaction, TxIn, TxOut,
 };
 use btct::{
+    airdrop::airdrop,
+    bid::{BidPolicy, BidStep},
+    buy::buy,
     cancel::cancel,
+    coin_select::CoinSelection,
     default,
+    etch::{etch, EtchOptions},
+    finalize::finalize,
+    inscribe::inscribe,
+    list::{list, ListAsset},
+    mint::mint,
     monitor::monitor,
-    send::send,
+    prepare::prepare,
+    send::{send, Asset},
     setting::{read_settings_from_file, Settings, SettingsSerde},
+    sighash::analyze,
     snipe::{snipe, Type},
     speed_up::speed_up,
+    wallet::derive,
+    watch::watch,
 };
 use clap::{ArgGroup, Args, Parser, Subcommand, ValueEnum};
 use env_logger::Env;
@@ -25,6 +38,9 @@ struct App {
     #[arg(short, long, default_value = "./config.toml")]
     /// Custom config path
     config: PathBuf,
+    #[arg(long, global = true)]
+    /// Coin selection, override the config
+    coin_selection: Option<CoinSelection>,
     #[command(subcommand)]
     command: Commands,
 }
@@ -57,14 +73,25 @@ enum Commands {
         // #[arg(short, long)]
         // fee_rate: u64,
         #[arg(short, long)]
-        /// [increase] than origin tx fee
+        /// [increase] than origin tx fee, sat/vb or percent by --step
         increase_rate: u64,
+        #[arg(long, default_value = "linear")]
+        step: BidStep,
+        #[arg(long, default_value_t = 10)]
+        /// Max replacements under monitor
+        max_rounds: u32,
+        #[arg(long)]
+        /// Max total fee of one replacement, sat
+        max_fee: Option<u64>,
+        #[arg(long)]
+        /// Max fee rate, sat/vb
+        max_fee_rate: Option<u64>,
         #[arg(short = 'b', long, default_value_t = false)]
         broadcast: bool,
         #[arg(long = "show", default_value_t = false)]
         show_tx: bool,
         #[arg(short, long, default_value_t = false)]
-        /// Skip replace confirm
+        /// Skip replace and broadcast confirm
         yes: bool,
         #[arg(short, long, default_value_t = false)]
         poison: bool,
@@ -86,15 +113,23 @@ enum Commands {
     SpeedUp {
         #[arg(short, long)]
         tx_id: String,
-        #[arg(short, long, default_value_t = 10)]
-        increase_rate: u64,
+        #[arg(short, long)]
+        /// Target fee rate of the tx (RBF) or the tx with its child (CPFP)
+        fee_rate: u64,
         #[arg(short = 'b', long, default_value_t = false)]
         broadcast: bool,
     },
     /// Prepare your wallet, generate <number> UTXO of <amount>
     Prepare {
+        #[arg(short, long)]
+        fee_rate: u64,
         #[arg(short, long, default_value_t = 6)]
         number: u64,
+        #[arg(short, long, value_delimiter = ',')]
+        /// One amount for all utxo or one per utxo, dummy 600, postage 546 [default: 600]
+        amount: Vec<u64>,
+        #[arg(short = 'b', long, default_value_t = false)]
+        broadcast: bool,
     },
     /// Cancel unconfirmed tx
     Cancel {
@@ -117,15 +152,173 @@ enum Commands {
     },
     /// Send btc, inscription and runes address
     Send {
+        #[arg(long, required_unless_present = "batch")]
+        addr: Option<String>,
+        #[arg(short, long)]
+        fee_rate: u64,
+        #[arg(
+            short,
+            long,
+            required_unless_present_any = ["inscription", "rune", "batch"],
+            conflicts_with_all = ["inscription", "rune", "batch"]
+        )]
+        /// Btc amount
+        amount: Option<f64>,
+        #[arg(long, conflicts_with_all = ["addr", "inscription", "rune"])]
+        /// Pay many addresses in one tx, csv (address,amount) or json file
+        batch: Option<PathBuf>,
+        #[arg(long, conflicts_with = "rune")]
+        /// Inscription id on the ordi address
+        inscription: Option<String>,
+        #[arg(long, requires = "rune_amount")]
+        /// Rune name on the ordi address
+        rune: Option<String>,
+        #[arg(long, requires = "rune")]
+        /// Rune amount with decimals, e.g. 1.5
+        rune_amount: Option<String>,
+        #[arg(short = 'b', long, default_value_t = false)]
+        broadcast: bool,
+    },
+    /// Send one rune to many addresses in one tx
+    Airdrop {
+        #[arg(long)]
+        rune: String,
+        #[arg(long)]
+        /// csv (address,amount) or json file, rune amount with decimals
+        batch: PathBuf,
+        #[arg(short, long)]
+        fee_rate: u64,
+        #[arg(short = 'b', long, default_value_t = false)]
+        broadcast: bool,
+    },
+    /// Mint a rune, repeat in a chain of child txs
+    Mint {
+        #[arg(long)]
+        rune: String,
+        #[arg(long)]
+        /// Receive address, default the ordi address
+        to: Option<String>,
+        #[arg(long)]
+        /// Runestone pointer, 0 the receiver or 2 the chain output
+        pointer: Option<u32>,
+        #[arg(short, long, default_value_t = 1)]
+        number: u64,
+        #[arg(short, long)]
+        fee_rate: u64,
+        #[arg(short = 'b', long, default_value_t = false)]
+        broadcast: bool,
+    },
+    /// Etch a rune, commit then reveal after 6 blocks
+    Etch {
+        #[arg(long)]
+        /// Spaced rune name, e.g. HELLO•WORLD
+        rune: String,
+        #[arg(long, default_value_t = 0)]
+        divisibility: u8,
+        #[arg(long)]
+        symbol: Option<char>,
+        #[arg(long)]
+        /// Premine with decimals
+        premine: Option<String>,
+        #[arg(long)]
+        /// Amount per mint with decimals
+        amount: Option<String>,
+        #[arg(long)]
+        /// Max mints
+        cap: Option<u128>,
+        #[arg(long)]
+        height_start: Option<u64>,
+        #[arg(long)]
+        height_end: Option<u64>,
+        #[arg(long)]
+        offset_start: Option<u64>,
+        #[arg(long)]
+        offset_end: Option<u64>,
+        #[arg(long, default_value_t = false)]
+        turbo: bool,
+        #[arg(long)]
+        /// Premine receive address, default the ordi address
+        to: Option<String>,
+        #[arg(short, long)]
+        fee_rate: u64,
+        #[arg(short = 'b', long, default_value_t = false)]
+        broadcast: bool,
+    },
+    /// Inscribe a file or every file of a directory, commit then reveal
+    Inscribe {
+        #[arg(long)]
+        file: PathBuf,
+        #[arg(long)]
+        /// e.g. text/plain;charset=utf-8, image/png, default by file extension
+        content_type: Option<String>,
+        #[arg(long)]
+        /// Parent inscription id on the ordi address, spent in the reveal
+        parent: Option<String>,
+        #[arg(long)]
+        /// Inscription receive address, default the ordi address
+        to: Option<String>,
+        #[arg(short, long)]
+        fee_rate: u64,
+        #[arg(short = 'b', long, default_value_t = false)]
+        broadcast: bool,
+    },
+    /// Sign a SINGLE|ANYONECANPAY sell psbt for an inscription or rune utxo
+    List {
+        #[arg(long)]
+        /// Inscription id or rune utxo (txid:vout) on the ordi address
+        asset: String,
+        #[arg(long)]
+        /// Price in btc, paid to the pay address
+        price: f64,
+        #[arg(long, default_value_t = false)]
+        /// Output hex instead of base64
+        hex: bool,
+        #[arg(short, long)]
+        /// Write the psbt to a file
+        output: Option<PathBuf>,
+    },
+    /// Fill a seller SINGLE|ANYONECANPAY psbt with dummy utxos
+    Buy {
         #[arg(long)]
-        addr: String,
+        /// Seller psbt in base64 or hex, or a file containing either
+        psbt: String,
         #[arg(short, long)]
         fee_rate: u64,
+        #[arg(short = 'b', long, default_value_t = false)]
+        broadcast: bool,
+    },
+    /// Finalize and broadcast a psbt exported by a watch-only wallet and signed offline
+    Finalize {
+        #[arg(long)]
+        /// Signed psbt in base64 or hex, or a file containing either
+        psbt: String,
+        #[arg(short = 'b', long, default_value_t = false)]
+        broadcast: bool,
+    },
+    /// Show the sighash of every input of a tx and which ones can be lifted
+    Analyze {
+        #[arg(long)]
+        txid: String,
+    },
+    /// Poll the mempool for listings matching the rules and snipe them
+    Watch {
+        #[arg(long)]
+        /// Toml file of [[rule]] with addr, inscription or rune and max_price, max_unit_price
+        rules: PathBuf,
         #[arg(short, long)]
-        amount: f64,
+        /// [increase] than origin tx fee
+        increase_rate: u64,
+        #[arg(long, default_value_t = 5)]
+        /// Seconds between inscription owner lookups
+        interval: u64,
         #[arg(short = 'b', long, default_value_t = false)]
         broadcast: bool,
     },
+    /// Wallet layouts
+    Wallet {
+        #[command(subcommand)]
+        command: WalletCommands,
+    },
     /// Check setting and wallet
     Monitor {
         #[arg(long)]
@@ -136,6 +329,15 @@ enum Commands {
     Check {},
 }
 
+#[derive(Subcommand)]
+enum WalletCommands {
+    /// List the first accounts of every preset, to match wallet_mode with a browser wallet
+    Derive {
+        #[arg(short, long, default_value_t = 3)]
+        number: u32,
+    },
+}
+
 fn main() -> anyhow::Result<()> {
     env_logger::Builder::from_env(Env::default().default_filter_or("info"))
         .format_target(false)
@@ -143,7 +345,7 @@ fn main() -> anyhow::Result<()> {
 
     let app = App::parse();
 
-    let settings = read_settings_from_file(app.config)?;
+    let settings = read_settings_from_file(app.config)?.with_coin_selection(app.coin_selection);
 
     match app.command {
         Commands::Snipe {
@@ -151,6 +353,10 @@ fn main() -> anyhow::Result<()> {
             addr,
             typ,
             increase_rate,
+            step,
+            max_rounds,
+            max_fee,
+            max_fee_rate,
             broadcast,
             show_tx,
             yes,
@@ -161,12 +367,20 @@ fn main() -> anyhow::Result<()> {
             monitor,
             split_recv,
         } => {
+            let wallet = settings.wallet()?;
             if let Err(err) = snipe(
                 settings,
+                wallet.as_ref(),
                 &tx_id.unwrap_or_default(),
                 &addr.unwrap_or_default(),
                 typ,
-                increase_rate,
+                BidPolicy {
+                    step,
+                    increase_rate,
+                    max_rounds,
+                    max_fee,
+                    max_fee_rate,
+                },
                 broadcast,
                 show_tx,
                 yes,
@@ -185,13 +399,20 @@ fn main() -> anyhow::Result<()> {
         }
         Commands::SpeedUp {
             tx_id,
-            increase_rate,
+            fee_rate,
             broadcast,
         } => {
-            // test(&settings, &tx_id)?;
-            // speed_up(settings, &tx_id, increase_rate, broadcast)?;
+            let wallet = settings.wallet()?;
+            speed_up(settings, wallet.as_ref(), &tx_id, fee_rate, broadcast)?;
+        }
+        Commands::Prepare {
+            fee_rate,
+            number,
+            amount,
+            broadcast,
+        } => {
+            prepare(settings, fee_rate, number, amount, broadcast)?;
         }
-        Commands::Prepare { number } => {}
         Commands::Cancel {
             increase_fee: increase_rate,
             postage,
@@ -200,8 +421,10 @@ fn main() -> anyhow::Result<()> {
             peek,
             cancel_addr,
         } => {
+            let wallet = settings.wallet()?;
             cancel(
                 settings,
+                wallet.as_ref(),
                 cancel_addr,
                 increase_rate,
                 postage,
@@ -214,12 +437,126 @@ fn main() -> anyhow::Result<()> {
             addr,
             amount,
             fee_rate,
+            batch,
+            inscription,
+            rune,
+            rune_amount,
+            broadcast,
+        } => {
+            let asset = match (amount, batch, inscription, rune, rune_amount) {
+                (Some(amount), _, _, _, _) => Asset::Btc(amount),
+                (_, Some(path), _, _, _) => Asset::Batch(path),
+                (_, _, Some(inscription), _, _) => Asset::Inscription(inscription),
+                (_, _, _, Some(name), Some(amount)) => Asset::Rune { name, amount },
+                _ => unreachable!(),
+            };
+            let wallet = settings.wallet()?;
+            send(settings, wallet.as_ref(), addr, asset, fee_rate, broadcast)?;
+        }
+        Commands::Airdrop {
+            rune,
+            batch,
+            fee_rate,
             broadcast,
         } => {
-            send(settings, &addr, amount, fee_rate, broadcast)?;
+            airdrop(settings, &rune, batch, fee_rate, broadcast)?;
+        }
+        Commands::Mint {
+            rune,
+            to,
+            pointer,
+            number,
+            fee_rate,
+            broadcast,
+        } => {
+            mint(settings, &rune, to, pointer, number, fee_rate, broadcast)?;
+        }
+        Commands::Etch {
+            rune,
+            divisibility,
+            symbol,
+            premine,
+            amount,
+            cap,
+            height_start,
+            height_end,
+            offset_start,
+            offset_end,
+            turbo,
+            to,
+            fee_rate,
+            broadcast,
+        } => {
+            let options = EtchOptions {
+                rune,
+                divisibility,
+                symbol,
+                premine,
+                amount,
+                cap,
+                height_start,
+                height_end,
+                offset_start,
+                offset_end,
+                turbo,
+                to,
+            };
+            etch(settings, options, fee_rate, broadcast)?;
+        }
+        Commands::Inscribe {
+            file,
+            content_type,
+            parent,
+            to,
+            fee_rate,
+            broadcast,
+        } => {
+            inscribe(
+                settings,
+                file,
+                content_type,
+                parent,
+                to,
+                fee_rate,
+                broadcast,
+            )?;
+        }
+        Commands::List {
+            asset,
+            price,
+            hex,
+            output,
+        } => {
+            list(settings, ListAsset::from_str(&asset)?, price, hex, output)?;
+        }
+        Commands::Buy {
+            psbt,
+            fee_rate,
+            broadcast,
+        } => {
+            buy(settings, &psbt, fee_rate, broadcast)?;
+        }
+        Commands::Finalize { psbt, broadcast } => {
+            finalize(settings, &psbt, broadcast)?;
+        }
+        Commands::Analyze { txid } => {
+            analyze(settings, &txid)?;
+        }
+        Commands::Watch {
+            rules,
+            increase_rate,
+            interval,
+            broadcast,
+        } => {
+            watch(settings, &rules, increase_rate, interval, broadcast)?;
+        }
+        Commands::Wallet {
+            command: WalletCommands::Derive { number },
+        } => {
+            derive(settings, number)?;
         }
         Commands::Monitor { txid, interval } => {
-            monitor(&settings, &txid, interval);
+            monitor(&settings, &txid, interval)?;
         }
     }
     Ok(())
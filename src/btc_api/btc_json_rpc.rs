@@ -2,7 +2,7 @@ use std::{fmt::format, num::NonZeroU32};
 
 use anyhow::{anyhow, bail};
 use axum::http::{HeaderMap, Method};
-use bitcoin::{Transaction, Txid};
+use bitcoin::{OutPoint, ScriptBuf, Transaction, Txid};
 use governor::{DefaultDirectRateLimiter, Quota, RateLimiter};
 use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize};
 
@@ -76,6 +76,20 @@ impl Client {
         };
         self.request(&params)
     }
+
+    /// Script of an unspent output in the chain, outputs only spent in the mempool included
+    pub fn get_tx_out_script(&self, out_point: &OutPoint) -> anyhow::Result<Option<ScriptBuf>> {
+        let params = GetTxOut {
+            txid: out_point.txid.to_string(),
+            n: out_point.vout,
+            include_mempool: false,
+        };
+        let Some(out) = self.request::<_, Option<TxOut>>(&params)? else {
+            return Ok(None);
+        };
+        Ok(Some(ScriptBuf::from_hex(&out.script_pub_key.hex)?))
+    }
+
     pub fn send_raw_tx(&self, hex: &str) -> anyhow::Result<String> {
         let params = SendTxParams {
             hexstring: hex.to_string(),
@@ -124,6 +138,26 @@ impl Api for GetRawTx {
     }
 }
 
+#[derive(Serialize, Deserialize)]
+struct GetTxOut {
+    txid: String,
+    n: u32,
+    include_mempool: bool,
+}
+
+impl Api for GetTxOut {
+    fn method_name(&self) -> &'static str {
+        "gettxout"
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct TxOut {
+    pub value: f64,
+    #[serde(rename = "scriptPubKey")]
+    pub script_pub_key: ScriptPubKey,
+}
+
 #[derive(Serialize, Deserialize)]
 struct ScriptPubKey {
     pub asm: String,
@@ -164,6 +164,15 @@ impl Client {
             .text()?;
         Ok(serde_json::from_str(&c)?)
     }
+
+    /// Last 10 txs entering the mempool
+    pub fn get_mempool_recent(&self) -> anyhow::Result<Vec<MempoolTx>> {
+        Ok(self
+            .http
+            .get(format!("{}/mempool/recent", self.base_uri()))
+            .send()?
+            .json()?)
+    }
 }
 
 impl UtxoApi for Client {
@@ -1,4 +1,4 @@
-use miniscript::bitcoin::{transaction::Version, Amount, Sequence, Txid};
+use miniscript::bitcoin::{transaction::Version, Address, Amount, Sequence, Txid};
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize)]
@@ -15,6 +15,16 @@ pub struct Utxo {
     pub vout: u32,
     pub status: Status,
     pub value: Amount,
+    /// Set by `get_utxos`
+    #[serde(skip)]
+    pub address: Option<Address>,
+}
+
+impl Utxo {
+    /// The address holding it, `default` if unknown
+    pub fn addr_or(&self, default: &Address) -> Address {
+        self.address.clone().unwrap_or_else(|| default.clone())
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -111,3 +121,11 @@ pub(crate) struct RBFResp {
     pub replacements: Option<Replacements>,
     pub replaces: Option<Vec<String>>,
 }
+
+#[derive(Serialize, Deserialize)]
+pub struct MempoolTx {
+    pub txid: String,
+    pub fee: u64,
+    pub vsize: u64,
+    pub value: u64,
+}
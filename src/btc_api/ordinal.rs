@@ -278,12 +278,22 @@ impl Client {
         }
         bail!("No inscription or rune in output")
     }
-    pub fn fetch_output(&self, out_point: &OutPoint) -> anyhow::Result<Ordinal> {
-        let mut o = self
+    pub fn output(&self, out_point: &OutPoint) -> anyhow::Result<Output> {
+        let o = self
             .http
             .get(format!("{}/output/{}", self.addr, out_point))
             .send()?
             .json::<Output>()?;
+        Ok(o)
+    }
+
+    /// Safe to spend as plain btc
+    pub(crate) fn is_cardinal(&self, out_point: &OutPoint) -> anyhow::Result<bool> {
+        Ok(self.output(out_point)?.is_cardinal())
+    }
+
+    pub fn fetch_output(&self, out_point: &OutPoint) -> anyhow::Result<Ordinal> {
+        let mut o = self.output(out_point)?;
         if !o.inscriptions.is_empty() {
             let inscription = o.inscriptions.pop().unwrap();
 
@@ -319,6 +329,15 @@ impl Client {
         Ok(Ordinal::None)
     }
 
+    pub fn fetch_inscription(&self, id: &str) -> anyhow::Result<Inscription> {
+        let o = self
+            .http
+            .get(format!("{}/inscription/{}", self.addr, id))
+            .send()?
+            .json::<Inscription>()?;
+        Ok(o)
+    }
+
     pub fn fetch_rune_id(&self, name: &str) -> anyhow::Result<RuneEntity> {
         let o = self
             .http
@@ -340,10 +359,20 @@ pub struct RuneEntry {
     pub premine: u64,
     pub spaced_rune: String,
     pub symbol: String,
+    #[serde(default)]
+    pub terms: Option<Terms>,
     pub timestamp: i64,
     pub turbo: bool,
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct Terms {
+    pub amount: Option<u128>,
+    pub cap: Option<u128>,
+    pub height: (Option<u64>, Option<u64>),
+    pub offset: (Option<u64>, Option<u64>),
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct RuneEntity {
     pub entry: RuneEntry,
@@ -364,6 +393,45 @@ pub struct Output {
     pub value: Amount,
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct Inscription {
+    pub id: String,
+    pub address: Option<String>,
+    pub satpoint: String,
+    pub value: Option<u64>,
+}
+
+impl Inscription {
+    /// (out_point, offset)
+    pub fn satpoint(&self) -> anyhow::Result<(OutPoint, u64)> {
+        let (out_point, offset) = self
+            .satpoint
+            .rsplit_once(':')
+            .ok_or(anyhow!("Invalid satpoint {}", self.satpoint))?;
+        Ok((OutPoint::from_str(out_point)?, offset.parse()?))
+    }
+}
+
+impl Output {
+    /// Raw balance of `spaced_rune`
+    pub fn rune_amount(&self, spaced_rune: &str) -> u128 {
+        self.runes
+            .iter()
+            .filter_map(|rune| match rune.as_slice() {
+                [RuneItem::Name(name), RuneItem::Info { amount, .. }] if name == spaced_rune => {
+                    Some(*amount as u128)
+                }
+                _ => None,
+            })
+            .sum()
+    }
+
+    /// No inscription and no rune, an output the indexer has not seen yet is not trusted
+    pub fn is_cardinal(&self) -> bool {
+        self.indexed && self.inscriptions.is_empty() && self.runes.is_empty()
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum RuneItem {
@@ -384,7 +452,7 @@ mod tests {
     use crate::{
         btc_api::{
             esplora,
-            ordinal::{fetch_outputs, Client, Ordinal, Output},
+            ordinal::{fetch_outputs, Client, Inscription, Ordinal, Output},
         },
         Print,
     };
@@ -397,6 +465,32 @@ mod tests {
         a.print();
     }
 
+    #[test]
+    fn test_satpoint() {
+        let str = r#"{"id":"e79134080a83fe3e0e06ed6990c5a9b63b362313341745707a2bff7d788a1375i0","address":"bc1pyf5f0r5eqxer5rdrwm98grgz5tem6k8xgtnm49he2m4kjhacrsms6p6888","satpoint":"24d006b4352792750fe2e7294cf9829db4e06cb11d1b4c5f03f9243c5622bc5f:3:100","value":546,"number":3}"#;
+        let inscription: Inscription = serde_json::from_str(str).unwrap();
+        let (out_point, offset) = inscription.satpoint().unwrap();
+        assert_eq!(
+            out_point.to_string(),
+            "24d006b4352792750fe2e7294cf9829db4e06cb11d1b4c5f03f9243c5622bc5f:3"
+        );
+        assert_eq!(offset, 100);
+    }
+
+    #[test]
+    fn test_is_cardinal() {
+        let rune = r#"{"address":"bc1pyf5f0r5eqxer5rdrwm98grgz5tem6k8xgtnm49he2m4kjhacrsms6p6888","indexed":true,"inscriptions":[],"runes":[["DOG•GO•TO•THE•MOON",{"amount":10000000000,"divisibility":5,"symbol":"🐕"}]],"sat_ranges":null,"script_pubkey":"OP_PUSHNUM_1 OP_PUSHBYTES_32 2268978e9901b23a0da376ca740d02a2f3bd58e642e7ba96f956eb695fb81c37","spent":false,"transaction":"24d006b4352792750fe2e7294cf9829db4e06cb11d1b4c5f03f9243c5622bc5f","value":546}"#;
+        let inscription = r#"{"address":"bc1pyf5f0r5eqxer5rdrwm98grgz5tem6k8xgtnm49he2m4kjhacrsms6p6888","indexed":true,"inscriptions":["e79134080a83fe3e0e06ed6990c5a9b63b362313341745707a2bff7d788a1375i0"],"runes":[],"sat_ranges":null,"script_pubkey":"OP_PUSHNUM_1 OP_PUSHBYTES_32 2268978e9901b23a0da376ca740d02a2f3bd58e642e7ba96f956eb695fb81c37","spent":false,"transaction":"24d006b4352792750fe2e7294cf9829db4e06cb11d1b4c5f03f9243c5622bc5f","value":20000}"#;
+        let unindexed = r#"{"address":"bc1pyf5f0r5eqxer5rdrwm98grgz5tem6k8xgtnm49he2m4kjhacrsms6p6888","indexed":false,"inscriptions":[],"runes":[],"sat_ranges":null,"script_pubkey":"OP_PUSHNUM_1 OP_PUSHBYTES_32 2268978e9901b23a0da376ca740d02a2f3bd58e642e7ba96f956eb695fb81c37","spent":false,"transaction":"24d006b4352792750fe2e7294cf9829db4e06cb11d1b4c5f03f9243c5622bc5f","value":20000}"#;
+        let cardinal = r#"{"address":"bc1pyf5f0r5eqxer5rdrwm98grgz5tem6k8xgtnm49he2m4kjhacrsms6p6888","indexed":true,"inscriptions":[],"runes":[],"sat_ranges":null,"script_pubkey":"OP_PUSHNUM_1 OP_PUSHBYTES_32 2268978e9901b23a0da376ca740d02a2f3bd58e642e7ba96f956eb695fb81c37","spent":false,"transaction":"24d006b4352792750fe2e7294cf9829db4e06cb11d1b4c5f03f9243c5622bc5f","value":20000}"#;
+
+        let is_cardinal = |s: &str| serde_json::from_str::<Output>(s).unwrap().is_cardinal();
+        assert!(!is_cardinal(rune));
+        assert!(!is_cardinal(inscription));
+        assert!(!is_cardinal(unindexed));
+        assert!(is_cardinal(cardinal));
+    }
+
     #[test]
     fn test_client() {
         let c = Client::new("https://javirbin.com");
@@ -0,0 +1,193 @@
+use anyhow::{anyhow, bail};
+use bitcoin::{
+    consensus::encode,
+    hashes::Hash,
+    key::XOnlyPublicKey,
+    secp256k1::{Message, Secp256k1},
+    sighash::{Prevouts, SighashCache},
+    taproot, Address, FeeRate, Psbt, TapSighashType, TxIn, TxOut, Witness,
+};
+
+use crate::{
+    btc_api::esplora::Prevout,
+    setting::Settings,
+    snipe::{build_fill_psbt, get_wallet_utxos},
+    utils::{print_snipe_table, read_psbt, select_confirm},
+    verify::verify_psbt,
+};
+
+/// Fill a seller's SINGLE|ANYONECANPAY listing with the same layout as snipe
+pub fn buy(settings: Settings, psbt: &str, fee_rate: u64, broadcast: bool) -> anyhow::Result<()> {
+    let ordi_api = settings.ordi_api();
+    let wallet = settings.wallet()?;
+    let pay_addr = wallet.pay_addr();
+    let ordi_addr = wallet.ordi_addr();
+    wallet.check();
+    let fee_rate = FeeRate::from_sat_per_vb(fee_rate).ok_or(anyhow!("fee_rate is invalid"))?;
+
+    let seller_psbt = read_psbt(psbt)?;
+    let seller_inputs = verify_seller_psbt(&settings, &seller_psbt)?;
+
+    let ordinals = ordi_api.fetch_outputs(
+        seller_inputs
+            .iter()
+            .map(|(txin, _)| &txin.previous_output)
+            .collect(),
+    )?;
+    if ordinals.len() != seller_inputs.len() {
+        bail!("Every seller input must carry an inscription or rune");
+    }
+    let ordinal_and_output = ordinals
+        .into_iter()
+        .map(|(i, ordi)| {
+            let (txin, prevout) = seller_inputs[i].clone();
+            let prevout = Prevout {
+                scriptpubkey: prevout.script_pubkey.to_hex_string(),
+                scriptpubkey_asm: "".to_string(),
+                scriptpubkey_type: "".to_string(),
+                scriptpubkey_address: Address::from_script(
+                    &prevout.script_pubkey,
+                    settings.network,
+                )?
+                .to_string(),
+                value: prevout.value,
+            };
+            Ok((
+                i,
+                ordi,
+                (txin, prevout),
+                seller_psbt.unsigned_tx.output[i].clone(),
+            ))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let selected = vec![true; ordinal_and_output.len()];
+    let is_rune = ordinal_and_output.iter().any(|(_, o, _, _)| o.is_rune());
+
+    let (can_utxos, dummy_utxos) = get_wallet_utxos(&settings, wallet.as_ref())?;
+    let mut psbt = build_fill_psbt(
+        &settings,
+        &ordinal_and_output,
+        &selected,
+        is_rune,
+        can_utxos,
+        dummy_utxos,
+        pay_addr,
+        wallet.change_addr(),
+        ordi_addr,
+        fee_rate,
+        None,
+        (
+            seller_psbt.unsigned_tx.version,
+            seller_psbt.unsigned_tx.lock_time,
+        ),
+    )?;
+
+    let ok = wallet.sign(&mut psbt)?;
+    if !ok {
+        bail!("Sign failed")
+    }
+    let fee = psbt.fee()?;
+    let signed_tx = psbt.clone().extract_tx()?;
+    verify_psbt(&psbt, &signed_tx)?;
+    let hex = encode::serialize_hex(&signed_tx);
+
+    log::info!(
+        "[{}] FeeRate: {:.1} sat/vb , TotalFee: {} sat , Size: {} vb",
+        signed_tx.txid(),
+        fee.to_sat() as f64 / signed_tx.vsize() as f64,
+        fee.to_sat(),
+        signed_tx.vsize()
+    );
+    print_snipe_table(&psbt, ordinal_and_output, settings.network);
+
+    if broadcast {
+        if select_confirm("Please confirm") {
+            settings.broadcast(&hex)?;
+        }
+    }
+    Ok(())
+}
+
+/// Every seller input must be an unspent taproot key spend signed with SINGLE|ANYONECANPAY
+/// and have its paired output. Returns the finalized inputs with their prevouts from chain.
+pub(crate) fn verify_seller_psbt(
+    settings: &Settings,
+    psbt: &Psbt,
+) -> anyhow::Result<Vec<(TxIn, TxOut)>> {
+    let btc_api = settings.btc_api();
+    let ordi_api = settings.ordi_api();
+    let secp = Secp256k1::verification_only();
+    let tx = &psbt.unsigned_tx;
+    if tx.input.is_empty() {
+        bail!("Seller psbt has no input");
+    }
+    if tx.output.len() < tx.input.len() {
+        bail!("Every seller input needs the output at the same index");
+    }
+
+    let mut seller_inputs = Vec::new();
+    for (i, (txin, input)) in tx.input.iter().zip(&psbt.inputs).enumerate() {
+        let out_point = txin.previous_output;
+        let prevout = btc_api
+            .get_btc_transaction(&out_point.txid.to_string())?
+            .output
+            .get(out_point.vout as usize)
+            .cloned()
+            .ok_or(anyhow!("Input {}: {} not found", i, out_point))?;
+        if input.witness_utxo.as_ref().is_some_and(|e| e != &prevout) {
+            bail!("Input {}: witness utxo does not match {}", i, out_point);
+        }
+        if ordi_api.output(&out_point)?.spent {
+            bail!("Input {}: {} is spent", i, out_point);
+        }
+        if !prevout.script_pubkey.is_p2tr() {
+            bail!("Input {}: only taproot sellers are supported", i);
+        }
+
+        let signature = match (&input.tap_key_sig, &input.final_script_witness) {
+            (Some(signature), _) => *signature,
+            (None, Some(witness)) if witness.len() == 1 => {
+                taproot::Signature::from_slice(witness.nth(0).unwrap())?
+            }
+            _ => bail!("Input {}: not signed", i),
+        };
+        if signature.hash_ty != TapSighashType::SinglePlusAnyoneCanPay {
+            bail!(
+                "Input {}: sighash is {}, expect SINGLE|ANYONECANPAY",
+                i,
+                signature.hash_ty
+            );
+        }
+        let hash = SighashCache::new(tx).taproot_key_spend_signature_hash(
+            i,
+            &Prevouts::One(i, &prevout),
+            signature.hash_ty,
+        )?;
+        let key = XOnlyPublicKey::from_slice(&prevout.script_pubkey.as_bytes()[2..])?;
+        secp.verify_schnorr(
+            &signature.sig,
+            &Message::from_digest(hash.to_byte_array()),
+            &key,
+        )
+        .map_err(|_| anyhow!("Input {}: invalid seller signature", i))?;
+        log::info!(
+            "[seller] Input: {} , Price: {} , To: {}",
+            out_point,
+            tx.output[i].value,
+            Address::from_script(&tx.output[i].script_pubkey, settings.network)
+                .map_or(tx.output[i].script_pubkey.to_asm_string(), |e| e
+                    .to_string())
+        );
+
+        seller_inputs.push((
+            TxIn {
+                previous_output: out_point,
+                script_sig: Default::default(),
+                sequence: txin.sequence,
+                witness: Witness::from_slice(&[signature.to_vec()]),
+            },
+            prevout,
+        ));
+    }
+    Ok(seller_inputs)
+}
@@ -8,16 +8,20 @@ use bitcoin::{
 use console_utils::input::select;
 
 use crate::{
-    btc_api::esplora,
+    btc_api::{esplora, esplora::Utxo},
+    coin_select::{Change, CoinSelector, SelectionTarget},
     default,
     dummy_transaction::DummyTransaction,
+    package::ancestor_package,
     setting::Settings,
-    snipe::get_utxos,
-    utils::{print_table, select_confirm},
+    snipe::{get_utxos, get_wallet_utxos},
+    utils::{export_psbt, print_table, select_confirm},
+    wallet::Wallet,
 };
 
 pub fn cancel(
     settings: Settings,
+    wallet: &dyn Wallet,
     cancel_addr: Option<String>,
     increase_fee: u64,
     postage: u64,
@@ -25,25 +29,29 @@ pub fn cancel(
     origin: bool, // todo
     peek: u64,
 ) -> anyhow::Result<()> {
-    let wallet = settings.wallet()?;
     let btc_api = settings.btc_api();
 
-    let pay_addr = cancel_addr.map_or(wallet.peek_addr(peek as u32), |e| {
-        Address::from_str(&e).unwrap().assume_checked()
-    });
+    // every discovered address unless one is picked
+    let addrs = match (cancel_addr, peek) {
+        (Some(addr), _) => vec![Address::from_str(&addr)?.require_network(settings.network)?],
+        (None, 0) => wallet.pay_addrs(),
+        (None, peek) => vec![wallet.peek_addr(peek as u32)],
+    };
 
     wallet.check();
 
-    let txs = btc_api.get_transactions(&pay_addr.to_string())?;
-
-    if txs.is_empty() {
+    let mut unconfirmed_txs: Vec<esplora::Transaction> = Vec::new();
+    for addr in &addrs {
+        for tx in btc_api.get_transactions(&addr.to_string())? {
+            if !tx.status.confirmed && !unconfirmed_txs.iter().any(|e| e.txid == tx.txid) {
+                unconfirmed_txs.push(tx);
+            }
+        }
+    }
+    if unconfirmed_txs.is_empty() {
         log::info!("No unconfirmed tx");
         return Ok(());
     }
-    let mut unconfirmed_txs = txs
-        .into_iter()
-        .filter(|e| !e.status.confirmed)
-        .collect::<Vec<_>>();
 
     let selected = select(
         "Please select txid: ",
@@ -67,20 +75,34 @@ pub fn cancel(
     //     return Ok(());
     // }
 
-    let network_fee = Amount::from_sat(selected_tx.fee + increase_fee);
+    // keep the rate of the tx being cancelled
+    let fee_rate = FeeRate::from_sat_per_kwu(selected_tx.fee * 1000 / selected_tx.weight);
+    let replaced_fee = Amount::from_sat(selected_tx.fee + increase_fee);
 
+    let (mut utxos, _) = get_wallet_utxos(&settings, wallet)?;
+    let pay_addrs = wallet.pay_addrs();
+    for addr in addrs.iter().filter(|e| !pay_addrs.contains(e)) {
+        utxos.append(&mut get_utxos(&settings, &addr.to_string())?.0);
+    }
     let mut unsigned_psbt = build_psbt(
         selected_tx,
-        &btc_api,
-        pay_addr,
-        network_fee,
+        &settings,
+        &addrs,
+        wallet.change_addr(),
+        utxos,
+        fee_rate,
+        replaced_fee,
         Amount::from_sat(postage),
         Amount::from_sat(dummy_utxo),
         origin,
+        &settings.coin_selection,
     )?;
 
     log::info!("[signed psbt] {}", unsigned_psbt.serialize_hex());
 
+    if wallet.is_watch_only() {
+        return export_psbt(wallet, &unsigned_psbt, settings.network);
+    }
     let ok = wallet.sign(&mut unsigned_psbt)?;
 
     if !ok {
@@ -101,34 +123,34 @@ pub fn cancel(
     Ok(())
 }
 
+/// Replace `unconfirmed_tx` with one spending its inputs of `addrs` back to us, dummy and
+/// postage utxos are kept as they are and everything else goes to `change_addr`. The fee
+/// reaches `fee_rate` for the package of unconfirmed parents and exceeds `replaced_fee` as
+/// BIP125 asks.
 pub(crate) fn build_psbt(
     unconfirmed_tx: esplora::Transaction,
-    btc_api: &esplora::Client,
-    addr: Address,
-    fee: Amount,
+    settings: &Settings,
+    addrs: &[Address],
+    change_addr: Address,
+    utxos: Vec<Utxo>,
+    fee_rate: FeeRate,
+    replaced_fee: Amount,
     postage: Amount,
     dummy_utxo: Amount,
     origin: bool,
+    coin_selector: &dyn CoinSelector,
 ) -> anyhow::Result<Psbt> {
-    let mut total_amount = Amount::ZERO;
-    let utxos = unconfirmed_tx
+    let vin = unconfirmed_tx
         .vin
         .into_iter()
-        .filter(|e| e.prevout.scriptpubkey_address == addr.to_string())
-        .map(|e| {
-            (
-                TxIn {
-                    previous_output: OutPoint {
-                        txid: e.txid,
-                        vout: e.vout,
-                    },
-                    script_sig: Default::default(),
-                    sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
-                    witness: Default::default(),
-                },
-                e.prevout.value,
-            )
-        });
+        .filter_map(|e| {
+            let addr = addrs
+                .iter()
+                .find(|addr| addr.to_string() == e.prevout.scriptpubkey_address)?;
+            Some((addr.clone(), e))
+        })
+        .collect::<Vec<_>>();
+    let parent_txids = vin.iter().map(|(_, e)| e.txid).collect::<Vec<_>>();
 
     let mut dummy_tx = DummyTransaction::new();
     let mut unsigned_tx = Transaction {
@@ -138,59 +160,87 @@ pub(crate) fn build_psbt(
         output: vec![],
     };
     let mut psbt_inputs = vec![];
+    let mut amount = Amount::ZERO;
 
-    for utxo in utxos {
-        unsigned_tx.input.push(utxo.0);
+    for (addr, e) in vin {
+        let value = e.prevout.value;
+        unsigned_tx.input.push(TxIn {
+            previous_output: OutPoint {
+                txid: e.txid,
+                vout: e.vout,
+            },
+            script_sig: Default::default(),
+            sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+            witness: Default::default(),
+        });
         dummy_tx.append_input(addr.clone(), None, None);
         psbt_inputs.push(Input {
-            witness_utxo: Some({
-                TxOut {
-                    value: utxo.1,
-                    script_pubkey: addr.script_pubkey(),
-                }
+            witness_utxo: Some(TxOut {
+                value,
+                script_pubkey: addr.script_pubkey(),
             }),
             ..default()
         });
 
-        if utxo.1 == dummy_utxo {
+        if value == dummy_utxo || value == postage {
             unsigned_tx.output.push(TxOut {
-                value: utxo.1,
-                script_pubkey: addr.script_pubkey(),
-            });
-            dummy_tx.append_output(addr.script_pubkey());
-        } else if utxo.1 == postage {
-            unsigned_tx.output.push(TxOut {
-                value: utxo.1,
+                value,
                 script_pubkey: addr.script_pubkey(),
             });
             dummy_tx.append_output(addr.script_pubkey());
         } else {
-            total_amount += utxo.1;
+            amount += value;
         }
     }
 
-    // collect
-    if total_amount.to_sat() > 0 {
-        unsigned_tx.output.push(TxOut {
-            value: total_amount,
-            script_pubkey: addr.script_pubkey(),
-        });
-    }
-
-    dummy_tx.append_output(addr.script_pubkey());
-    // change
+    // change, collects everything else
     unsigned_tx.output.push(TxOut {
         value: Amount::ZERO,
-        script_pubkey: addr.script_pubkey(),
+        script_pubkey: change_addr.script_pubkey(),
     });
-    dummy_tx.append_output(addr.script_pubkey());
+    dummy_tx.append_output(change_addr.script_pubkey());
 
-    let (utxos, _) = get_utxos(btc_api, &addr.to_string())?;
-    let mut amount = Amount::ZERO;
-    let mut ok = false;
-    for utxo in utxos {
-        let yes = select_confirm(&format!("{}:{}:{}", utxo.txid, utxo.vout, utxo.value));
-        if !yes {
+    let package = ancestor_package(&settings.btc_api(), &parent_txids)?;
+    let network_fee = |vsize: u64| {
+        package
+            .child_fee(vsize, fee_rate)
+            .max(replaced_fee + FeeRate::BROADCAST_MIN.fee_vb(vsize).unwrap())
+    };
+
+    let vsize = dummy_tx.vsize() as u64;
+    // what the parents and BIP125 ask on top of the rate
+    let extra_fee = network_fee(vsize)
+        .checked_sub(fee_rate.fee_vb(vsize).unwrap())
+        .unwrap_or(Amount::ZERO);
+    let target = SelectionTarget::new(
+        extra_fee.checked_sub(amount).unwrap_or(Amount::ZERO),
+        fee_rate,
+        &dummy_tx,
+        &change_addr,
+    );
+    let mut utxos = coin_selector.select(utxos, &target).into_iter();
+    loop {
+        match Change::settle(
+            amount,
+            Amount::ZERO,
+            network_fee(dummy_tx.vsize() as u64),
+            target.change_fee(),
+            target.dust,
+        ) {
+            // a tx needs at least one output
+            Some(Change::Dropped) if unsigned_tx.output.len() == 1 => {}
+            Some(change) => {
+                let index = unsigned_tx.output.len() - 1;
+                change.apply(&mut unsigned_tx.output, index);
+                break;
+            }
+            None => {}
+        }
+
+        let Some(utxo) = utxos.next() else {
+            bail!("No utxo or not enough")
+        };
+        if !select_confirm(&format!("{}:{}:{}", utxo.txid, utxo.vout, utxo.value)) {
             continue;
         }
         unsigned_tx.input.push(TxIn {
@@ -202,37 +252,15 @@ pub(crate) fn build_psbt(
             sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
             witness: Default::default(),
         });
-
         psbt_inputs.push(Input {
-            witness_utxo: Some({
-                TxOut {
-                    value: utxo.value,
-                    script_pubkey: addr.script_pubkey(),
-                }
+            witness_utxo: Some(TxOut {
+                value: utxo.value,
+                script_pubkey: utxo.addr_or(&change_addr).script_pubkey(),
             }),
             ..default()
         });
-
-        dummy_tx.append_input(addr.clone(), None, None);
-
+        dummy_tx.append_input(utxo.addr_or(&change_addr), None, None);
         amount += utxo.value;
-
-        let network_fee = fee;
-
-        if let Some(unfilled) = amount.checked_sub(network_fee + Amount::from_sat(1000)) {
-            unsigned_tx.output.last_mut().unwrap().value = unfilled;
-            ok = true;
-            break;
-        }
-    }
-    if !ok {
-        unsigned_tx.output.last_mut().unwrap().value -= fee + Amount::from_sat(1000);
-        //
-        // bail!("No uxto or not enough")
-    }
-
-    if unsigned_tx.output.last().unwrap().value < addr.script_pubkey().dust_value() {
-        unsigned_tx.output.pop();
     }
 
     let o_len = unsigned_tx.output.len();
@@ -245,10 +273,5 @@ pub(crate) fn build_psbt(
         inputs: psbt_inputs,
         outputs: vec![default(); o_len],
     };
-    // if total_amount <= network_fee + Amount::from_sat(1000) {
-    //     // 余额不够
-    // } else {
-    //     for out in psbt.unsigned_tx.output {}
-    // }
     Ok(psbt)
 }
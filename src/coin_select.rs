@@ -0,0 +1,332 @@
+use std::cmp::Reverse;
+
+use bitcoin::{Address, Amount, FeeRate, TxOut};
+use clap::ValueEnum;
+use serde::Deserialize;
+
+use crate::{btc_api::esplora::Utxo, dummy_transaction::DummyTransaction};
+
+const BNB_TOTAL_TRIES: usize = 100_000;
+
+/// What the selected utxos have to pay for.
+pub(crate) struct SelectionTarget {
+    /// Outputs except change, minus inputs already in the tx
+    pub(crate) amount: Amount,
+    pub(crate) fee_rate: FeeRate,
+    /// vsize of the tx before any selected input, without change
+    pub(crate) base_vsize: u64,
+    /// vsize added by one selected input
+    pub(crate) input_vsize: u64,
+    /// vsize added by the change output
+    pub(crate) change_vsize: u64,
+    pub(crate) dust: Amount,
+}
+
+impl SelectionTarget {
+    /// `dummy_tx` is the tx before any selected input, change output included.
+    pub(crate) fn new(
+        amount: Amount,
+        fee_rate: FeeRate,
+        dummy_tx: &DummyTransaction,
+        change_addr: &Address,
+    ) -> Self {
+        let change_script = change_addr.script_pubkey();
+        let change_vsize = TxOut {
+            value: Amount::ZERO,
+            script_pubkey: change_script.clone(),
+        }
+        .size() as u64;
+
+        let mut with_input = DummyTransaction(dummy_tx.0.clone());
+        with_input.append_input(change_addr.clone(), None, None);
+
+        Self {
+            amount,
+            fee_rate,
+            base_vsize: (dummy_tx.vsize() as u64).saturating_sub(change_vsize),
+            input_vsize: (with_input.vsize() - dummy_tx.vsize()) as u64,
+            change_vsize,
+            dust: change_script.dust_value(),
+        }
+    }
+
+    fn fee(&self, vsize: u64) -> Amount {
+        self.fee_rate.fee_vb(vsize).unwrap()
+    }
+
+    /// Fee of the change output alone
+    pub(crate) fn change_fee(&self) -> Amount {
+        self.fee(self.change_vsize)
+    }
+
+    /// Needed without change
+    fn changeless_target(&self) -> i64 {
+        (self.amount + self.fee(self.base_vsize)).to_sat() as i64
+    }
+
+    /// Value a utxo adds after paying for its own input
+    fn effective_value(&self, utxo: &Utxo) -> i64 {
+        utxo.value.to_sat() as i64 - self.fee(self.input_vsize).to_sat() as i64
+    }
+
+    /// Excess below this is cheaper to leave to miners than to create change
+    fn cost_of_change(&self) -> i64 {
+        (self.fee(self.change_vsize) + self.dust).to_sat() as i64
+    }
+}
+
+/// What is left for the change output once the outputs and the fee are paid.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum Change {
+    Output(Amount),
+    /// Below dust, the change output is dropped and the excess left to miners
+    Dropped,
+}
+
+impl Change {
+    /// `fee` is for the tx with the change output, None if `amount` doesn't cover the tx
+    /// even without change.
+    pub(crate) fn settle(
+        amount: Amount,
+        need: Amount,
+        fee: Amount,
+        change_fee: Amount,
+        dust: Amount,
+    ) -> Option<Self> {
+        match amount.checked_sub(need + fee) {
+            Some(unfilled) if unfilled >= dust => Some(Change::Output(unfilled)),
+            _ if amount + change_fee >= need + fee => Some(Change::Dropped),
+            _ => None,
+        }
+    }
+
+    /// Set or remove the change output at `index`
+    pub(crate) fn apply(self, outputs: &mut Vec<TxOut>, index: usize) {
+        match self {
+            Change::Output(value) => outputs[index].value = value,
+            Change::Dropped => {
+                outputs.remove(index);
+            }
+        }
+    }
+}
+
+/// Orders utxos so that spending them front to back covers the target, the tx builders
+/// stop at the first utxo that fills it.
+pub(crate) trait CoinSelector {
+    fn select(&self, utxos: Vec<Utxo>, target: &SelectionTarget) -> Vec<Utxo>;
+}
+
+/// Fewest inputs in most cases
+pub(crate) struct LargestFirst;
+
+impl CoinSelector for LargestFirst {
+    fn select(&self, mut utxos: Vec<Utxo>, _target: &SelectionTarget) -> Vec<Utxo> {
+        utxos.sort_by_key(|e| Reverse(e.value));
+        utxos
+    }
+}
+
+/// Consolidate small utxos while fees are low
+pub(crate) struct SmallestFirst;
+
+impl CoinSelector for SmallestFirst {
+    fn select(&self, mut utxos: Vec<Utxo>, _target: &SelectionTarget) -> Vec<Utxo> {
+        utxos.sort_by_key(|e| e.value);
+        utxos
+    }
+}
+
+/// Spend by confirmation height, unconfirmed last
+pub(crate) struct OldestFirst;
+
+impl CoinSelector for OldestFirst {
+    fn select(&self, mut utxos: Vec<Utxo>, _target: &SelectionTarget) -> Vec<Utxo> {
+        utxos.sort_by_key(|e| (e.status.block_height.unwrap_or(u64::MAX), Reverse(e.value)));
+        utxos
+    }
+}
+
+/// The smallest single utxo covering the target, otherwise largest first
+pub(crate) struct MinInputs;
+
+impl CoinSelector for MinInputs {
+    fn select(&self, utxos: Vec<Utxo>, target: &SelectionTarget) -> Vec<Utxo> {
+        let mut utxos = LargestFirst.select(utxos, target);
+        let need = target.changeless_target();
+        if let Some(index) = utxos
+            .iter()
+            .rposition(|e| target.effective_value(e) >= need)
+        {
+            let utxo = utxos.remove(index);
+            utxos.insert(0, utxo);
+        }
+        utxos
+    }
+}
+
+/// Search a changeless set (Bitcoin Core's branch and bound), otherwise largest first
+pub(crate) struct BranchAndBound;
+
+impl BranchAndBound {
+    /// Index of the selected utxos, `utxos` sorted by effective value descending
+    fn search(effective_values: &[i64], target: i64, upper_bound: i64) -> Option<Vec<usize>> {
+        let mut remaining = effective_values.iter().sum::<i64>();
+        if remaining < target {
+            return None;
+        }
+        let mut current = 0i64;
+        // included or not of effective_values[..selection.len()]
+        let mut selection: Vec<bool> = Vec::with_capacity(effective_values.len());
+        let mut best: Option<(i64, Vec<bool>)> = None;
+
+        for _ in 0..BNB_TOTAL_TRIES {
+            let mut backtrack = false;
+            if current + remaining < target || current > upper_bound {
+                backtrack = true;
+            } else if current >= target {
+                let waste = current - target;
+                if best
+                    .as_ref()
+                    .map_or(true, |(best_waste, _)| waste < *best_waste)
+                {
+                    best = Some((waste, selection.clone()));
+                }
+                backtrack = true;
+            }
+
+            if backtrack {
+                while selection.last() == Some(&false) {
+                    selection.pop();
+                    remaining += effective_values[selection.len()];
+                }
+                let Some(last) = selection.last_mut() else {
+                    // all branches explored
+                    break;
+                };
+                *last = false;
+                current -= effective_values[selection.len() - 1];
+            } else {
+                let index = selection.len();
+                remaining -= effective_values[index];
+                current += effective_values[index];
+                selection.push(true);
+            }
+        }
+
+        best.map(|(_, selection)| {
+            selection
+                .into_iter()
+                .enumerate()
+                .filter_map(|(i, included)| included.then_some(i))
+                .collect()
+        })
+    }
+}
+
+impl CoinSelector for BranchAndBound {
+    fn select(&self, utxos: Vec<Utxo>, target: &SelectionTarget) -> Vec<Utxo> {
+        let (mut positive, negative): (Vec<_>, Vec<_>) = utxos
+            .into_iter()
+            .partition(|e| target.effective_value(e) > 0);
+        positive.sort_by_key(|e| Reverse(e.value));
+
+        let effective_values = positive
+            .iter()
+            .map(|e| target.effective_value(e))
+            .collect::<Vec<_>>();
+        let changeless_target = target.changeless_target();
+        let Some(selected) = Self::search(
+            &effective_values,
+            changeless_target,
+            changeless_target + target.cost_of_change(),
+        ) else {
+            log::info!("[coin selection] No changeless solution, fallback to largest first");
+            positive.extend(negative);
+            return positive;
+        };
+        log::info!("[coin selection] Changeless with {} inputs", selected.len());
+
+        let mut rest = Vec::new();
+        let mut chosen = Vec::new();
+        for (i, utxo) in positive.into_iter().enumerate() {
+            if selected.contains(&i) {
+                chosen.push(utxo);
+            } else {
+                rest.push(utxo);
+            }
+        }
+        // fallback if the estimate was a little short
+        chosen.extend(rest);
+        chosen.extend(negative);
+        chosen
+    }
+}
+
+#[derive(Debug, Deserialize, Default, Copy, Clone, PartialEq, Eq, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum CoinSelection {
+    #[default]
+    LargestFirst,
+    SmallestFirst,
+    OldestFirst,
+    MinInputs,
+    BranchAndBound,
+}
+
+impl CoinSelector for CoinSelection {
+    fn select(&self, utxos: Vec<Utxo>, target: &SelectionTarget) -> Vec<Utxo> {
+        match self {
+            CoinSelection::LargestFirst => LargestFirst.select(utxos, target),
+            CoinSelection::SmallestFirst => SmallestFirst.select(utxos, target),
+            CoinSelection::OldestFirst => OldestFirst.select(utxos, target),
+            CoinSelection::MinInputs => MinInputs.select(utxos, target),
+            CoinSelection::BranchAndBound => BranchAndBound.select(utxos, target),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bnb_exact() {
+        let values = [10_000, 7_000, 5_000, 3_000, 1_000];
+        let selected = BranchAndBound::search(&values, 8_000, 8_100).unwrap();
+        let sum = selected.iter().map(|i| values[*i]).sum::<i64>();
+        assert_eq!(sum, 8_000);
+    }
+
+    #[test]
+    fn test_bnb_window() {
+        let values = [10_000, 6_000, 2_500];
+        // 8500 is the only sum in [8400, 8600]
+        let selected = BranchAndBound::search(&values, 8_400, 8_600).unwrap();
+        assert_eq!(selected, vec![1, 2]);
+        assert!(BranchAndBound::search(&values, 8_700, 8_800).is_none());
+        assert!(BranchAndBound::search(&values, 20_000, 30_000).is_none());
+    }
+
+    #[test]
+    fn test_change_settle() {
+        let settle = |amount| {
+            Change::settle(
+                Amount::from_sat(amount),
+                Amount::from_sat(10_000),
+                Amount::from_sat(1_000),
+                Amount::from_sat(200),
+                Amount::from_sat(330),
+            )
+        };
+        assert_eq!(
+            settle(12_000),
+            Some(Change::Output(Amount::from_sat(1_000)))
+        );
+        assert_eq!(settle(11_330), Some(Change::Output(Amount::from_sat(330))));
+        // exact match without the change output
+        assert_eq!(settle(10_800), Some(Change::Dropped));
+        assert_eq!(settle(11_100), Some(Change::Dropped));
+        assert_eq!(settle(10_799), None);
+    }
+}
@@ -0,0 +1,182 @@
+use anyhow::anyhow;
+use bitcoin::{
+    constants::MAX_SCRIPT_ELEMENT_SIZE,
+    hashes::Hash,
+    key::{Keypair, XOnlyPublicKey},
+    opcodes,
+    script::{self, Instruction, PushBytesBuf},
+    secp256k1::{rand, All, Message, Secp256k1},
+    sighash::{Prevouts, SighashCache},
+    taproot::{self, ControlBlock, LeafVersion, TaprootBuilder, TaprootSpendInfo},
+    Address, Network, PrivateKey, ScriptBuf, TapLeafHash, TapSighashType, Transaction, TxOut,
+    Witness,
+};
+
+const PROTOCOL_ID: [u8; 3] = *b"ord";
+
+/// Envelope tags
+pub(crate) const CONTENT_TYPE_TAG: u8 = 1;
+pub(crate) const PARENT_TAG: u8 = 3;
+pub(crate) const RUNE_TAG: u8 = 13;
+
+/// Commit output locked to `<key> OP_CHECKSIG OP_FALSE OP_IF "ord" .. OP_ENDIF`, spent by the
+/// reveal through the script path with a one-off key.
+pub(crate) struct Envelope {
+    keypair: Keypair,
+    script: ScriptBuf,
+    spend_info: TaprootSpendInfo,
+}
+
+impl Envelope {
+    /// `fields` are (tag, value) pushed before the body
+    pub(crate) fn new(
+        secp: &Secp256k1<All>,
+        fields: &[(u8, Vec<u8>)],
+        body: Option<&[u8]>,
+    ) -> anyhow::Result<Self> {
+        let keypair = Keypair::new(secp, &mut rand::thread_rng());
+        let (public_key, _) = keypair.x_only_public_key();
+        let script = envelope_script(public_key, fields, body);
+        let spend_info = TaprootBuilder::new()
+            .add_leaf(0, script.clone())?
+            .finalize(secp, public_key)
+            .map_err(|_| anyhow!("Taproot finalize failed"))?;
+        Ok(Self {
+            keypair,
+            script,
+            spend_info,
+        })
+    }
+
+    /// Commit address
+    pub(crate) fn address(&self, network: Network) -> Address {
+        Address::p2tr_tweaked(self.spend_info.output_key(), network)
+    }
+
+    /// Spends the commit output if the reveal never confirms
+    pub(crate) fn recovery_key(&self, network: Network) -> String {
+        PrivateKey::new(self.keypair.secret_key(), network).to_wif()
+    }
+
+    fn control_block(&self) -> ControlBlock {
+        self.spend_info
+            .control_block(&(self.script.clone(), LeafVersion::TapScript))
+            .unwrap()
+    }
+
+    fn witness(&self, signature: &[u8]) -> Witness {
+        let mut witness = Witness::new();
+        witness.push(signature);
+        witness.push(self.script.as_bytes());
+        witness.push(self.control_block().serialize());
+        witness
+    }
+
+    /// Same size as the signed witness, for fee estimation
+    pub(crate) fn dummy_witness(&self) -> Witness {
+        self.witness(&[0; 64])
+    }
+
+    /// Sign input `input_index` of the reveal spending the commit output
+    pub(crate) fn sign(
+        &self,
+        secp: &Secp256k1<All>,
+        tx: &mut Transaction,
+        input_index: usize,
+        prevouts: &[TxOut],
+    ) -> anyhow::Result<()> {
+        let hash = SighashCache::new(&*tx).taproot_script_spend_signature_hash(
+            input_index,
+            &Prevouts::All(prevouts),
+            TapLeafHash::from_script(&self.script, LeafVersion::TapScript),
+            TapSighashType::Default,
+        )?;
+        let signature = taproot::Signature {
+            sig: secp.sign_schnorr(&Message::from_digest(hash.to_byte_array()), &self.keypair),
+            hash_ty: TapSighashType::Default,
+        };
+        tx.input[input_index].witness = self.witness(&signature.to_vec());
+        Ok(())
+    }
+}
+
+/// Any input revealing an `OP_FALSE OP_IF "ord"` envelope, that is an inscription
+pub(crate) fn has_envelope(tx: &Transaction) -> bool {
+    tx.input
+        .iter()
+        .filter_map(|e| e.witness.tapscript())
+        .any(|script| {
+            let instructions = script
+                .instructions()
+                .filter_map(Result::ok)
+                .collect::<Vec<_>>();
+            instructions.windows(3).any(|e| {
+                matches!(
+                    e,
+                    [Instruction::PushBytes(f), Instruction::Op(op), Instruction::PushBytes(id)]
+                        if f.is_empty() && *op == opcodes::all::OP_IF && id.as_bytes() == PROTOCOL_ID
+                )
+            })
+        })
+}
+
+fn envelope_script(
+    public_key: XOnlyPublicKey,
+    fields: &[(u8, Vec<u8>)],
+    body: Option<&[u8]>,
+) -> ScriptBuf {
+    let mut builder = script::Builder::new()
+        .push_slice(public_key.serialize())
+        .push_opcode(opcodes::all::OP_CHECKSIG)
+        .push_opcode(opcodes::OP_FALSE)
+        .push_opcode(opcodes::all::OP_IF)
+        .push_slice(PROTOCOL_ID);
+    for (tag, value) in fields {
+        for chunk in value.chunks(MAX_SCRIPT_ELEMENT_SIZE) {
+            builder = builder
+                .push_slice([*tag])
+                .push_slice(PushBytesBuf::try_from(chunk.to_vec()).unwrap());
+        }
+    }
+    if let Some(body) = body {
+        builder = builder.push_slice([]);
+        for chunk in body.chunks(MAX_SCRIPT_ELEMENT_SIZE) {
+            builder = builder.push_slice(PushBytesBuf::try_from(chunk.to_vec()).unwrap());
+        }
+    }
+    builder.push_opcode(opcodes::all::OP_ENDIF).into_script()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_envelope_script() {
+        let secp = Secp256k1::new();
+        let (public_key, _) = Keypair::new(&secp, &mut rand::thread_rng()).x_only_public_key();
+        let body = vec![7u8; MAX_SCRIPT_ELEMENT_SIZE + 1];
+        let script = envelope_script(
+            public_key,
+            &[(CONTENT_TYPE_TAG, b"text/plain".to_vec())],
+            Some(&body),
+        );
+
+        let pushes = script
+            .instructions()
+            .map(|e| match e.unwrap() {
+                Instruction::PushBytes(push) => push.as_bytes().to_vec(),
+                Instruction::Op(op) => vec![op.to_u8()],
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(pushes[0], public_key.serialize().to_vec());
+        assert_eq!(pushes[2], Vec::<u8>::new()); // OP_FALSE
+        assert_eq!(pushes[4], b"ord".to_vec());
+        assert_eq!(pushes[5], vec![CONTENT_TYPE_TAG]);
+        assert_eq!(pushes[6], b"text/plain".to_vec());
+        assert_eq!(pushes[7], Vec::<u8>::new()); // body
+        assert_eq!(pushes[8].len(), MAX_SCRIPT_ELEMENT_SIZE);
+        assert_eq!(pushes[9].len(), 1);
+        assert_eq!(pushes[10], vec![opcodes::all::OP_ENDIF.to_u8()]);
+    }
+}
@@ -0,0 +1,209 @@
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail};
+use bitcoin::{
+    absolute::LockTime, consensus::encode, transaction::Version, Address, Amount, FeeRate,
+    OutPoint, Psbt, ScriptBuf, Sequence, Transaction, TxIn, TxOut,
+};
+use ordinals::{Etching, Runestone, SpacedRune, Terms};
+
+use crate::{
+    constant::POSTAGE,
+    envelope::{Envelope, RUNE_TAG},
+    monitor::wait_confirmations,
+    rune::{parse_rune_amount, rune_commitment},
+    send::build_psbt_with_inputs,
+    setting::Settings,
+    snipe,
+    utils::{print_table, select_confirm},
+};
+
+/// The commit output has to be this old when the reveal is mined
+const COMMIT_CONFIRMATIONS: u16 = 6;
+
+pub struct EtchOptions {
+    /// Spaced rune name, e.g. HELLO•WORLD
+    pub rune: String,
+    pub divisibility: u8,
+    pub symbol: Option<char>,
+    /// With decimals
+    pub premine: Option<String>,
+    /// Per mint, with decimals
+    pub amount: Option<String>,
+    pub cap: Option<u128>,
+    pub height_start: Option<u64>,
+    pub height_end: Option<u64>,
+    pub offset_start: Option<u64>,
+    pub offset_end: Option<u64>,
+    pub turbo: bool,
+    /// Premine receiver, default the ordi address
+    pub to: Option<String>,
+}
+
+impl EtchOptions {
+    fn etching(&self) -> anyhow::Result<Etching> {
+        let spaced_rune = SpacedRune::from_str(&self.rune)
+            .map_err(|e| anyhow!("Invalid rune {}: {}", self.rune, e))?;
+        let premine = self
+            .premine
+            .as_ref()
+            .map(|e| parse_rune_amount(e, self.divisibility as u32))
+            .transpose()?;
+        let amount = self
+            .amount
+            .as_ref()
+            .map(|e| parse_rune_amount(e, self.divisibility as u32))
+            .transpose()?;
+
+        let has_terms = self.cap.is_some()
+            || self.height_start.is_some()
+            || self.height_end.is_some()
+            || self.offset_start.is_some()
+            || self.offset_end.is_some();
+        if has_terms && amount.is_none() {
+            bail!("Please set the mint amount");
+        }
+        let terms = amount.map(|amount| Terms {
+            amount: Some(amount),
+            cap: self.cap,
+            height: (self.height_start, self.height_end),
+            offset: (self.offset_start, self.offset_end),
+        });
+        if premine.is_none() && terms.is_none() {
+            bail!("Please set premine or mint terms");
+        }
+
+        Ok(Etching {
+            divisibility: Some(self.divisibility),
+            premine,
+            rune: Some(spaced_rune.rune),
+            spacers: Some(spaced_rune.spacers),
+            symbol: self.symbol,
+            terms,
+            turbo: self.turbo,
+        })
+    }
+}
+
+/// Commit to the rune name in a taproot envelope, reveal the etching once the commit has
+/// enough confirmations.
+pub fn etch(
+    settings: Settings,
+    options: EtchOptions,
+    fee_rate: u64,
+    broadcast: bool,
+) -> anyhow::Result<()> {
+    let wallet = settings.wallet()?;
+    let secp = wallet.ctx();
+    let to_addr = match &options.to {
+        Some(addr) => Address::from_str(addr)?.require_network(settings.network)?,
+        None => wallet.ordi_addr(),
+    };
+    let fee_rate = FeeRate::from_sat_per_vb(fee_rate).ok_or(anyhow!("fee_rate is invalid"))?;
+    let etching = options.etching()?;
+    let rune = etching.rune.unwrap();
+
+    let envelope = Envelope::new(secp, &[(RUNE_TAG, rune_commitment(rune))], None)?;
+    let commit_addr = envelope.address(settings.network);
+    log::info!("[etch] Rune: {}, Commit: {}", options.rune, commit_addr);
+    log::info!(
+        "[etch] Recovery key: {} , keep it until the reveal is confirmed",
+        envelope.recovery_key(settings.network)
+    );
+
+    let runestone = Runestone {
+        edicts: vec![],
+        etching: Some(etching),
+        mint: None,
+        pointer: None,
+    }
+    .encipher();
+
+    // reveal: commit output -> premine receiver, runestone
+    let mut reveal_tx = Transaction {
+        version: Version::TWO,
+        lock_time: LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint::null(),
+            script_sig: Default::default(),
+            sequence: Sequence::from_height(COMMIT_CONFIRMATIONS - 1),
+            witness: envelope.dummy_witness(),
+        }],
+        output: vec![
+            TxOut {
+                value: POSTAGE,
+                script_pubkey: to_addr.script_pubkey(),
+            },
+            TxOut {
+                value: Amount::ZERO,
+                script_pubkey: ScriptBuf::from_bytes(runestone.into_bytes()),
+            },
+        ],
+    };
+    let reveal_fee = fee_rate.fee_vb(reveal_tx.vsize() as u64).unwrap();
+    let commit_output = TxOut {
+        value: POSTAGE + reveal_fee,
+        script_pubkey: commit_addr.script_pubkey(),
+    };
+
+    let (utxos, _) = snipe::get_wallet_utxos(&settings, wallet.as_ref())?;
+    let mut commit_psbt = build_psbt_with_inputs(
+        wallet.change_addr(),
+        vec![],
+        vec![commit_output.clone()],
+        fee_rate,
+        utxos,
+        &settings.coin_selection,
+    )?;
+    let ok = wallet.sign(&mut commit_psbt)?;
+    if !ok {
+        bail!("Sign failed")
+    }
+    print_table(&commit_psbt, settings.network);
+    let commit_fee = commit_psbt.fee()?;
+    let commit_tx = commit_psbt.extract_tx()?;
+    let commit_hex = encode::serialize_hex(&commit_tx);
+
+    reveal_tx.input[0].previous_output = OutPoint {
+        txid: commit_tx.txid(),
+        vout: 0,
+    };
+    reveal_tx.input[0].witness = Default::default();
+    print_table(
+        &Psbt::from_unsigned_tx(reveal_tx.clone())?,
+        settings.network,
+    );
+    envelope.sign(secp, &mut reveal_tx, 0, &[commit_output])?;
+    let reveal_hex = encode::serialize_hex(&reveal_tx);
+
+    log::info!(
+        "[commit] {} , FeeRate: {:.1} sat/vb , TotalFee: {} sat , Size: {} vb",
+        commit_tx.txid(),
+        commit_fee.to_sat() as f64 / commit_tx.vsize() as f64,
+        commit_fee.to_sat(),
+        commit_tx.vsize()
+    );
+    log::info!(
+        "[reveal] {} , FeeRate: {:.1} sat/vb , TotalFee: {} sat , Size: {} vb",
+        reveal_tx.txid(),
+        reveal_fee.to_sat() as f64 / reveal_tx.vsize() as f64,
+        reveal_fee.to_sat(),
+        reveal_tx.vsize()
+    );
+    log::info!("[reveal] {}", reveal_hex);
+
+    if broadcast {
+        if select_confirm("Please confirm") {
+            settings.broadcast(&commit_hex)?;
+            // the reveal is mined in the next block at the earliest
+            wait_confirmations(
+                &settings,
+                &commit_tx.txid().to_string(),
+                COMMIT_CONFIRMATIONS as u64 - 1,
+                60,
+            )?;
+            settings.broadcast(&reveal_hex)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,38 @@
+use anyhow::bail;
+use bitcoin::consensus::encode;
+
+use crate::{
+    setting::Settings,
+    utils::{print_table, read_psbt, select_confirm},
+    verify::verify_psbt,
+};
+
+/// Finalize a psbt exported by a watch-only wallet and signed offline
+pub fn finalize(settings: Settings, psbt: &str, broadcast: bool) -> anyhow::Result<()> {
+    let wallet = settings.wallet()?;
+    let mut psbt = read_psbt(psbt)?;
+    if !wallet.finalize(&mut psbt)? {
+        bail!("Finalize failed, some inputs are not signed")
+    }
+    let fee = psbt.fee()?;
+    let signed_tx = psbt.clone().extract_tx()?;
+    verify_psbt(&psbt, &signed_tx)?;
+    let hex = encode::serialize_hex(&signed_tx);
+
+    log::info!(
+        "[{}] FeeRate: {:.1} sat/vb , TotalFee: {} sat , Size: {} vb",
+        signed_tx.txid(),
+        fee.to_sat() as f64 / signed_tx.vsize() as f64,
+        fee.to_sat(),
+        signed_tx.vsize()
+    );
+    print_table(&psbt, settings.network);
+    log::info!("[signed] {}", hex);
+
+    if broadcast {
+        if select_confirm("Please confirm") {
+            settings.broadcast(&hex)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,303 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::{anyhow, bail};
+use bitcoin::{
+    absolute::LockTime, consensus::encode, hashes::Hash, policy::MAX_STANDARD_TX_WEIGHT,
+    transaction::Version, Address, Amount, FeeRate, OutPoint, Psbt, Sequence, Transaction, TxIn,
+    TxOut, Txid, Witness,
+};
+
+use crate::{
+    constant::POSTAGE,
+    envelope::{Envelope, CONTENT_TYPE_TAG, PARENT_TAG},
+    send::{build_psbt_with_inputs, inscription_transfer},
+    setting::Settings,
+    snipe,
+    utils::{print_table, select_confirm},
+};
+
+/// Inscribe `path`, a file or every file of a directory, in one commit and one reveal.
+/// Commit output `i` funds envelope `i`, the reveal sends inscription `i` to output `i`
+/// (after the parent outputs when `parent` is set) of the ordi address or `to_addr`.
+pub fn inscribe(
+    settings: Settings,
+    path: PathBuf,
+    content_type: Option<String>,
+    parent: Option<String>,
+    to_addr: Option<String>,
+    fee_rate: u64,
+    broadcast: bool,
+) -> anyhow::Result<()> {
+    let wallet = settings.wallet()?;
+    let secp = wallet.ctx();
+    let ordi_addr = wallet.ordi_addr();
+    let to_addr = match to_addr {
+        Some(addr) => Address::from_str(&addr)?.require_network(settings.network)?,
+        None => ordi_addr.clone(),
+    };
+    let fee_rate = FeeRate::from_sat_per_vb(fee_rate).ok_or(anyhow!("fee_rate is invalid"))?;
+
+    let files = read_files(&path)?;
+    let parent_field = parent
+        .as_ref()
+        .map(|id| Ok::<_, anyhow::Error>((PARENT_TAG, parent_value(id)?)))
+        .transpose()?;
+
+    let mut envelopes = Vec::new();
+    for file in &files {
+        let content_type = match &content_type {
+            Some(content_type) => content_type.as_str(),
+            None => guess_content_type(file)
+                .ok_or(anyhow!("Unknown content type of {}", file.display()))?,
+        };
+        let body = fs::read(file)?;
+        if body.is_empty() {
+            bail!("{} is empty", file.display());
+        }
+        let mut fields = vec![(CONTENT_TYPE_TAG, content_type.as_bytes().to_vec())];
+        fields.extend(parent_field.clone());
+        let envelope = Envelope::new(secp, &fields, Some(&body))?;
+        log::info!(
+            "[inscribe] {} ({}, {} bytes), Commit: {}",
+            file.display(),
+            content_type,
+            body.len(),
+            envelope.address(settings.network)
+        );
+        log::info!(
+            "[inscribe] Recovery key: {} , keep it until the reveal is confirmed",
+            envelope.recovery_key(settings.network)
+        );
+        envelopes.push(envelope);
+    }
+
+    // the parent goes back to the ordi address in front of the children
+    let (parent_inputs, parent_outputs) = match &parent {
+        Some(id) => inscription_transfer(&settings, &ordi_addr, id, ordi_addr.clone())?,
+        None => (vec![], vec![]),
+    };
+    let mut reveal_tx = Transaction {
+        version: Version::TWO,
+        lock_time: LockTime::ZERO,
+        input: vec![],
+        output: parent_outputs,
+    };
+    let mut prevouts = Vec::new();
+    for (addr, out_point, value) in &parent_inputs {
+        reveal_tx.input.push(TxIn {
+            previous_output: *out_point,
+            script_sig: Default::default(),
+            sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+            witness: Witness::from_slice(&[[0; 64]]),
+        });
+        prevouts.push(TxOut {
+            value: *value,
+            script_pubkey: addr.script_pubkey(),
+        });
+    }
+    for envelope in &envelopes {
+        reveal_tx.input.push(TxIn {
+            previous_output: OutPoint::null(),
+            script_sig: Default::default(),
+            sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+            witness: envelope.dummy_witness(),
+        });
+        reveal_tx.output.push(TxOut {
+            value: POSTAGE,
+            script_pubkey: to_addr.script_pubkey(),
+        });
+    }
+    if reveal_tx.weight().to_wu() > MAX_STANDARD_TX_WEIGHT as u64 {
+        bail!(
+            "Reveal is {} vb, more than the standard limit, please split the files",
+            reveal_tx.vsize()
+        );
+    }
+
+    // the last commit output also pays the reveal fee
+    let reveal_fee = fee_rate.fee_vb(reveal_tx.vsize() as u64).unwrap();
+    let mut commit_outputs = envelopes
+        .iter()
+        .map(|e| TxOut {
+            value: POSTAGE,
+            script_pubkey: e.address(settings.network).script_pubkey(),
+        })
+        .collect::<Vec<_>>();
+    commit_outputs.last_mut().unwrap().value += reveal_fee;
+
+    let (utxos, _) = snipe::get_wallet_utxos(&settings, wallet.as_ref())?;
+    let mut commit_psbt = build_psbt_with_inputs(
+        wallet.change_addr(),
+        vec![],
+        commit_outputs.clone(),
+        fee_rate,
+        utxos,
+        &settings.coin_selection,
+    )?;
+    let ok = wallet.sign(&mut commit_psbt)?;
+    if !ok {
+        bail!("Sign failed")
+    }
+    print_table(&commit_psbt, settings.network);
+    let commit_fee = commit_psbt.fee()?;
+    let commit_tx = commit_psbt.extract_tx()?;
+    let commit_hex = encode::serialize_hex(&commit_tx);
+
+    let offset = parent_inputs.len();
+    for (i, input) in reveal_tx.input.iter_mut().enumerate() {
+        input.witness = Default::default();
+        if i >= offset {
+            input.previous_output = OutPoint {
+                txid: commit_tx.txid(),
+                vout: (i - offset) as u32,
+            };
+        }
+    }
+    prevouts.extend(commit_outputs);
+
+    let mut reveal_psbt = Psbt::from_unsigned_tx(reveal_tx)?;
+    for (input, prevout) in reveal_psbt.inputs.iter_mut().zip(&prevouts) {
+        input.witness_utxo = Some(prevout.clone());
+    }
+    print_table(&reveal_psbt, settings.network);
+    if offset > 0 {
+        wallet.sign_all(&mut reveal_psbt)?;
+        if reveal_psbt.inputs[..offset]
+            .iter()
+            .any(|e| e.final_script_witness.is_none())
+        {
+            bail!("Sign parent failed")
+        }
+    }
+    let mut reveal_tx = reveal_psbt.extract_tx_unchecked_fee_rate();
+    for (i, envelope) in envelopes.iter().enumerate() {
+        envelope.sign(secp, &mut reveal_tx, offset + i, &prevouts)?;
+    }
+    let reveal_hex = encode::serialize_hex(&reveal_tx);
+
+    log::info!(
+        "[commit] {} , FeeRate: {:.1} sat/vb , TotalFee: {} sat , Size: {} vb",
+        commit_tx.txid(),
+        commit_fee.to_sat() as f64 / commit_tx.vsize() as f64,
+        commit_fee.to_sat(),
+        commit_tx.vsize()
+    );
+    log::info!(
+        "[reveal] {} , FeeRate: {:.1} sat/vb , TotalFee: {} sat , Size: {} vb",
+        reveal_tx.txid(),
+        reveal_fee.to_sat() as f64 / reveal_tx.vsize() as f64,
+        reveal_fee.to_sat(),
+        reveal_tx.vsize()
+    );
+    for (i, file) in files.iter().enumerate() {
+        log::info!(
+            "[inscribe] {} -> {}i{}",
+            file.display(),
+            reveal_tx.txid(),
+            i
+        );
+    }
+    if let Some(parent) = parent {
+        log::info!(
+            "[inscribe] Parent: {}, Postage: {}",
+            parent,
+            prevouts[..offset].iter().map(|e| e.value).sum::<Amount>()
+        );
+    }
+
+    if broadcast {
+        if select_confirm("Please confirm") {
+            settings.broadcast(&commit_hex)?;
+            settings.broadcast(&reveal_hex)?;
+        }
+    }
+    Ok(())
+}
+
+/// Files of a directory sorted by name, or the file itself
+fn read_files(path: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let mut files = Vec::new();
+    for entry in fs::read_dir(path)? {
+        let path = entry?.path();
+        if path.is_file() {
+            files.push(path);
+        }
+    }
+    if files.is_empty() {
+        bail!("No file in {}", path.display());
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Txid bytes then the index in little endian without trailing zeros
+fn parent_value(id: &str) -> anyhow::Result<Vec<u8>> {
+    let (txid, index) = id
+        .rsplit_once('i')
+        .ok_or(anyhow!("Invalid inscription id {}", id))?;
+    let txid = Txid::from_str(txid)?;
+    let index = u32::from_str(index)?;
+    let mut value = txid.to_byte_array().to_vec();
+    let index = index.to_le_bytes();
+    let len = index.iter().rposition(|e| *e != 0).map_or(0, |e| e + 1);
+    value.extend_from_slice(&index[..len]);
+    Ok(value)
+}
+
+fn guess_content_type(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    let content_type = match extension.as_str() {
+        "txt" => "text/plain;charset=utf-8",
+        "html" => "text/html;charset=utf-8",
+        "css" => "text/css",
+        "js" => "text/javascript",
+        "json" => "application/json",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "avif" => "image/avif",
+        "mp3" => "audio/mpeg",
+        "mp4" => "video/mp4",
+        "glb" => "model/gltf-binary",
+        _ => return None,
+    };
+    Some(content_type)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parent_value() {
+        let txid = "1111111111111111111111111111111111111111111111111111111111111100";
+        let value = parent_value(&format!("{}i0", txid)).unwrap();
+        assert_eq!(value.len(), 32);
+        assert_eq!(value[0], 0x00);
+        assert_eq!(value[31], 0x11);
+
+        let value = parent_value(&format!("{}i256", txid)).unwrap();
+        assert_eq!(value[32..], [0x00, 0x01]);
+
+        assert!(parent_value(txid).is_err());
+    }
+
+    #[test]
+    fn test_guess_content_type() {
+        assert_eq!(guess_content_type(Path::new("a/1.PNG")), Some("image/png"));
+        assert_eq!(
+            guess_content_type(Path::new("1.txt")),
+            Some("text/plain;charset=utf-8")
+        );
+        assert_eq!(guess_content_type(Path::new("1")), None);
+    }
+}
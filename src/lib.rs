@@ -14,18 +14,34 @@ pub mod broadcast;
 
 pub mod check;
 pub(crate) mod dummy_transaction;
+pub(crate) mod envelope;
+pub mod etch;
+pub mod inscribe;
+pub mod list;
 
 pub(crate) mod utils;
 
 pub mod prepare;
 
+pub mod airdrop;
+pub(crate) mod batch;
+pub mod bid;
+pub mod buy;
 pub mod cancel;
+pub mod coin_select;
 mod demo;
+pub mod finalize;
 pub mod send;
+pub mod sighash;
 pub mod speed_up;
+pub(crate) mod tracker;
+pub(crate) mod verify;
+pub mod watch;
 
 pub(crate) mod error;
+pub mod mint;
 pub mod monitor;
+pub(crate) mod package;
 
 pub mod audio;
 
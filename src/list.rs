@@ -0,0 +1,174 @@
+use std::{fs, path::PathBuf, str::FromStr};
+
+use anyhow::{anyhow, bail};
+use bitcoin::{
+    absolute::LockTime,
+    psbt::{Input, PsbtSighashType},
+    transaction::Version,
+    Amount, Denomination, OutPoint, Psbt, ScriptBuf, Sequence, TapSighashType, Transaction, TxIn,
+    TxOut,
+};
+
+use crate::{
+    default, send::inscription_transfer, setting::Settings, utils::print_table, wallet::Wallet,
+};
+
+pub enum ListAsset {
+    Inscription(String),
+    /// Rune utxo on the ordi address
+    Rune(OutPoint),
+}
+
+/// Seller psbt: input 0 is the asset, output 0 pays `price` to the pay address.
+/// Signed with SINGLE|ANYONECANPAY so a buyer can add inputs and outputs around it.
+pub fn list(
+    settings: Settings,
+    asset: ListAsset,
+    price: f64,
+    hex: bool,
+    output: Option<PathBuf>,
+) -> anyhow::Result<()> {
+    let wallet = settings.wallet()?;
+    let ordi_addr = wallet.ordi_addr();
+    let price = Amount::from_btc(price)?;
+
+    let (out_point, value) = match asset {
+        ListAsset::Inscription(id) => {
+            let (inputs, _) = inscription_transfer(&settings, &ordi_addr, &id, ordi_addr.clone())?;
+            let (_, out_point, value) = inputs[0].clone();
+            (out_point, value)
+        }
+        ListAsset::Rune(out_point) => {
+            let output = settings.ordi_api().output(&out_point)?;
+            if output.address != ordi_addr.to_string() {
+                bail!("Output {} is not on {}", out_point, ordi_addr);
+            }
+            if output.spent {
+                bail!("Output {} is spent", out_point);
+            }
+            if output.runes.is_empty() || !output.inscriptions.is_empty() {
+                bail!("Output {} must carry runes only", out_point);
+            }
+            log::info!("[rune] {}, Postage: {}", out_point, output.value);
+            (out_point, output.value)
+        }
+    };
+
+    let pay_script = wallet.pay_addr().script_pubkey();
+    let dust = pay_script.dust_value();
+    if price < dust {
+        bail!("Price {} is below dust {}", price, dust);
+    }
+    let psbt = build_listing_psbt(
+        wallet.as_ref(),
+        out_point,
+        TxOut {
+            value,
+            script_pubkey: ordi_addr.script_pubkey(),
+        },
+        price,
+        pay_script,
+    )?;
+    print_table(&psbt, settings.network);
+    log::info!(
+        "[list] {} for {}",
+        out_point,
+        price.display_in(Denomination::Bitcoin).show_denomination()
+    );
+
+    let encoded = if hex {
+        psbt.serialize_hex()
+    } else {
+        psbt.to_string()
+    };
+    match output {
+        Some(path) => {
+            fs::write(&path, &encoded)?;
+            log::info!("[list] Saved to {}", path.display());
+        }
+        None => println!("{}", encoded),
+    }
+    Ok(())
+}
+
+/// Sign `out_point` with SINGLE|ANYONECANPAY against one output paying `price` to `pay_script`
+pub(crate) fn build_listing_psbt(
+    wallet: &dyn Wallet,
+    out_point: OutPoint,
+    prevout: TxOut,
+    price: Amount,
+    pay_script: ScriptBuf,
+) -> anyhow::Result<Psbt> {
+    let tx = Transaction {
+        version: Version::TWO,
+        lock_time: LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: out_point,
+            sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+            ..default()
+        }],
+        output: vec![TxOut {
+            value: price,
+            script_pubkey: pay_script,
+        }],
+    };
+
+    let mut psbt = Psbt {
+        unsigned_tx: tx,
+        version: 0,
+        xpub: Default::default(),
+        proprietary: Default::default(),
+        unknown: Default::default(),
+        inputs: vec![Input {
+            witness_utxo: Some(prevout),
+            sighash_type: Some(PsbtSighashType::from(
+                TapSighashType::SinglePlusAnyoneCanPay,
+            )),
+            ..default()
+        }],
+        outputs: vec![default(); 1],
+    };
+
+    let ok = wallet.sign_swap(&mut psbt)?;
+    if !ok {
+        bail!("Sign failed")
+    }
+    Ok(psbt)
+}
+
+impl FromStr for ListAsset {
+    type Err = anyhow::Error;
+
+    /// `<txid>i<index>` is an inscription, `<txid>:<vout>` a rune utxo
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(out_point) = OutPoint::from_str(s) {
+            return Ok(ListAsset::Rune(out_point));
+        }
+        if s.len() > 65 && s.as_bytes()[64] == b'i' {
+            return Ok(ListAsset::Inscription(s.to_string()));
+        }
+        Err(anyhow!(
+            "Expect an inscription id or a rune utxo, got {}",
+            s
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_list_asset() {
+        let txid = "1111111111111111111111111111111111111111111111111111111111111111";
+        assert!(matches!(
+            ListAsset::from_str(&format!("{}i0", txid)).unwrap(),
+            ListAsset::Inscription(_)
+        ));
+        assert!(matches!(
+            ListAsset::from_str(&format!("{}:1", txid)).unwrap(),
+            ListAsset::Rune(OutPoint { vout: 1, .. })
+        ));
+        assert!(ListAsset::from_str(txid).is_err());
+    }
+}
@@ -0,0 +1,304 @@
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail};
+use bitcoin::{
+    absolute::LockTime, consensus::encode, psbt::Input, transaction::Version, Address, Amount,
+    FeeRate, OutPoint, Psbt, ScriptBuf, Sequence, Transaction, TxIn, TxOut,
+};
+use ordinals::{RuneId, Runestone};
+
+use crate::{
+    btc_api::ordinal::{RuneEntity, Terms},
+    constant::POSTAGE,
+    default,
+    dummy_transaction::DummyTransaction,
+    send::build_psbt_with_inputs,
+    setting::Settings,
+    snipe,
+    utils::{print_table, select_confirm},
+};
+
+/// Mempool ancestor limit
+const MAX_CHAIN_LEN: u64 = 25;
+
+/// Outputs of a mint tx: receiver, runestone, chain
+const CHAIN_OUTPUT: u32 = 2;
+
+/// Mint `number` times, the first tx funds the whole chain and every child
+/// spends the chain output of its parent.
+pub fn mint(
+    settings: Settings,
+    name: &str,
+    to_addr: Option<String>,
+    pointer: Option<u32>,
+    number: u64,
+    fee_rate: u64,
+    broadcast: bool,
+) -> anyhow::Result<()> {
+    if number == 0 || number > MAX_CHAIN_LEN {
+        bail!("Mint number must be in 1..={}", MAX_CHAIN_LEN);
+    }
+    let wallet = settings.wallet()?;
+    let pay_addr = wallet.pay_addr();
+    let to_addr = match to_addr {
+        Some(addr) => Address::from_str(&addr)?.require_network(settings.network)?,
+        None => wallet.ordi_addr(),
+    };
+    let fee_rate = FeeRate::from_sat_per_vb(fee_rate).ok_or(anyhow!("fee_rate is invalid"))?;
+
+    let rune = settings.ordi_api().fetch_rune_id(name)?;
+    let height = settings.btc_api().get_latest_block_height()?;
+    check_mintable(&rune, height + 1, number)?;
+    let rune_id = RuneId::from_str(&rune.id)?;
+
+    let (chain_runestone, last_runestone) = mint_runestones(rune_id, pointer, number)?;
+
+    // every child: chain input -> receiver, runestone, chain output
+    let mut dummy_tx = DummyTransaction::new();
+    dummy_tx.append_input(pay_addr.clone(), None, None);
+    dummy_tx.append_output(to_addr.script_pubkey());
+    dummy_tx.append_output(chain_runestone.clone());
+    dummy_tx.append_output(pay_addr.script_pubkey());
+    let child_cost = POSTAGE + fee_rate.fee_vb(dummy_tx.vsize() as u64).unwrap();
+
+    let mut outputs = vec![
+        TxOut {
+            value: POSTAGE,
+            script_pubkey: to_addr.script_pubkey(),
+        },
+        TxOut {
+            value: Amount::ZERO,
+            script_pubkey: if number > 1 {
+                chain_runestone.clone()
+            } else {
+                last_runestone.clone()
+            },
+        },
+    ];
+    let mut chain_value = child_cost * (number - 1);
+    if number > 1 {
+        outputs.push(TxOut {
+            value: chain_value,
+            script_pubkey: pay_addr.script_pubkey(),
+        });
+    }
+    let (utxos, _) = snipe::get_wallet_utxos(&settings, wallet.as_ref())?;
+    let mut psbts = vec![build_psbt_with_inputs(
+        wallet.change_addr(),
+        vec![],
+        outputs,
+        fee_rate,
+        utxos,
+        &settings.coin_selection,
+    )?];
+
+    for _ in 1..number {
+        let parent = &psbts.last().unwrap().unsigned_tx;
+        let previous_output = OutPoint {
+            txid: parent.txid(),
+            vout: CHAIN_OUTPUT,
+        };
+        chain_value = chain_value - child_cost;
+        let runestone = if chain_value > Amount::ZERO {
+            chain_runestone.clone()
+        } else {
+            last_runestone.clone()
+        };
+        psbts.push(build_child_psbt(
+            previous_output,
+            child_cost + chain_value,
+            &pay_addr,
+            &to_addr,
+            runestone,
+            chain_value,
+        ));
+    }
+
+    let mut hexes = Vec::new();
+    for psbt in psbts.iter_mut() {
+        let ok = wallet.sign(psbt)?;
+        if !ok {
+            bail!("Sign failed")
+        }
+        let fee = psbt.fee()?;
+        let signed_tx = psbt.clone().extract_tx()?;
+        log::info!(
+            "[{}] FeeRate: {:.1} sat/vb , TotalFee: {} sat , Size: {} vb",
+            signed_tx.txid(),
+            fee.to_sat() as f64 / signed_tx.vsize() as f64,
+            fee.to_sat(),
+            signed_tx.vsize()
+        );
+        print_table(psbt, settings.network);
+        hexes.push(encode::serialize_hex(&signed_tx));
+    }
+
+    if broadcast {
+        if select_confirm(&format!("Please confirm {} mints", number)) {
+            for hex in hexes {
+                settings.broadcast(&hex)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Runestones of the txs with a chain output and of the last tx. `pointer` 0 sends every mint
+/// to the receiver, 2 carries the runes along the chain output and the last tx, without
+/// pointer, sends them all to the receiver.
+fn mint_runestones(
+    rune_id: RuneId,
+    pointer: Option<u32>,
+    number: u64,
+) -> anyhow::Result<(ScriptBuf, ScriptBuf)> {
+    match pointer {
+        None | Some(0) => {}
+        Some(CHAIN_OUTPUT) if number > 1 => {}
+        Some(CHAIN_OUTPUT) => bail!("Pointer 2 is the chain output, there is none for 1 mint"),
+        Some(1) => bail!("Pointer 1 is the runestone, the minted runes would be burned"),
+        Some(e) => bail!("Pointer {} is out of range, must be 0 or 2", e),
+    }
+    let runestone = |pointer| {
+        let runestone = Runestone {
+            edicts: vec![],
+            etching: None,
+            mint: Some(rune_id),
+            pointer,
+        }
+        .encipher();
+        ScriptBuf::from_bytes(runestone.into_bytes())
+    };
+    Ok((runestone(pointer), runestone(None)))
+}
+
+/// The last child has no chain output, its remainder goes to miners.
+fn build_child_psbt(
+    previous_output: OutPoint,
+    value: Amount,
+    pay_addr: &Address,
+    to_addr: &Address,
+    runestone: ScriptBuf,
+    chain_value: Amount,
+) -> Psbt {
+    let mut unsigned_tx = Transaction {
+        version: Version::TWO,
+        lock_time: LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output,
+            script_sig: Default::default(),
+            sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+            witness: Default::default(),
+        }],
+        output: vec![
+            TxOut {
+                value: POSTAGE,
+                script_pubkey: to_addr.script_pubkey(),
+            },
+            TxOut {
+                value: Amount::ZERO,
+                script_pubkey: runestone,
+            },
+        ],
+    };
+    if chain_value > Amount::ZERO {
+        unsigned_tx.output.push(TxOut {
+            value: chain_value,
+            script_pubkey: pay_addr.script_pubkey(),
+        });
+    }
+
+    let o_len = unsigned_tx.output.len();
+    Psbt {
+        unsigned_tx,
+        version: 0,
+        xpub: Default::default(),
+        proprietary: Default::default(),
+        unknown: Default::default(),
+        inputs: vec![Input {
+            witness_utxo: Some(TxOut {
+                value,
+                script_pubkey: pay_addr.script_pubkey(),
+            }),
+            ..default()
+        }],
+        outputs: vec![default(); o_len],
+    }
+}
+
+fn check_mintable(rune: &RuneEntity, height: u64, number: u64) -> anyhow::Result<()> {
+    let name = &rune.entry.spaced_rune;
+    if !rune.mintable {
+        bail!("Rune {} is not mintable", name);
+    }
+    let Some(terms) = &rune.entry.terms else {
+        bail!("Rune {} has no mint terms", name);
+    };
+    if !is_open(terms, rune.entry.block, height) {
+        bail!("Rune {} is not open for minting at height {}", name, height);
+    }
+    if let Some(cap) = terms.cap {
+        let left = cap.saturating_sub(rune.entry.mints as u128);
+        if left < number as u128 {
+            bail!("Rune {} only {} mints left", name, left);
+        }
+    }
+    log::info!(
+        "[mint] {} Amount: {} x {}, Mints: {}/{}",
+        name,
+        terms.amount.unwrap_or_default(),
+        number,
+        rune.entry.mints,
+        terms.cap.map_or("-".to_string(), |e| e.to_string())
+    );
+    Ok(())
+}
+
+/// Start inclusive, end exclusive, offsets are relative to the etching block
+fn is_open(terms: &Terms, etching_block: u64, height: u64) -> bool {
+    let starts = [terms.height.0, terms.offset.0.map(|e| etching_block + e)];
+    let ends = [terms.height.1, terms.offset.1.map(|e| etching_block + e)];
+    starts.into_iter().flatten().all(|start| height >= start)
+        && ends.into_iter().flatten().all(|end| height < end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_open() {
+        let terms = Terms {
+            amount: Some(1000),
+            cap: Some(100),
+            height: (Some(840_000), Some(850_000)),
+            offset: (None, Some(5_000)),
+        };
+        assert!(!is_open(&terms, 840_000, 839_999));
+        assert!(is_open(&terms, 840_000, 840_000));
+        assert!(is_open(&terms, 840_000, 844_999));
+        // offset end
+        assert!(!is_open(&terms, 840_000, 845_000));
+
+        let terms = Terms {
+            amount: Some(1000),
+            cap: None,
+            height: (None, None),
+            offset: (None, None),
+        };
+        assert!(is_open(&terms, 840_000, 900_000));
+    }
+
+    #[test]
+    fn test_mint_runestones() {
+        let id = RuneId::from_str("840000:1").unwrap();
+        let (chain, last) = mint_runestones(id, None, 3).unwrap();
+        assert_eq!(chain, last);
+        let (chain, last) = mint_runestones(id, Some(CHAIN_OUTPUT), 3).unwrap();
+        assert_ne!(chain, last);
+        assert_eq!(last, mint_runestones(id, None, 1).unwrap().1);
+        assert!(mint_runestones(id, Some(0), 1).is_ok());
+        assert!(mint_runestones(id, Some(CHAIN_OUTPUT), 1).is_err());
+        assert!(mint_runestones(id, Some(1), 3).is_err());
+        assert!(mint_runestones(id, Some(3), 3).is_err());
+    }
+}
@@ -1,33 +1,49 @@
 use std::{thread, time::Duration};
 
-use anyhow::anyhow;
+use crate::{
+    audio,
+    setting::Settings,
+    tracker::{wait_tx, TxOutcome},
+};
 
-use crate::{audio, setting::Settings};
-
-pub fn monitor(settings: &Settings, txid: &str, interval: u64) -> anyhow::Result<bool> {
-    let rpc_api = settings
-        .rpc_api()
-        .ok_or(anyhow!("Please setting [rpc_url]"))?;
-    thread::sleep(Duration::from_secs(5));
+/// Wait for `txid` over the tx tracker until it is confirmed or replaced
+pub fn monitor(settings: &Settings, txid: &str, interval: u64) -> anyhow::Result<TxOutcome> {
+    let mut tracker = settings.tx_tracker()?;
+    let outcome = wait_tx(tracker.as_mut(), txid, Duration::from_secs(interval))?;
+    match outcome {
+        TxOutcome::Confirmed => {
+            log::info!("[{}] Tx has confirmed", txid);
+            audio::play_confirmed();
+        }
+        TxOutcome::Replaced { .. } => {
+            log::info!("[{}] Tx has replaced", txid);
+            audio::play_replaced();
+        }
+    }
+    Ok(outcome)
+}
 
+/// Block until `txid` has `confirmations` confirmations
+pub(crate) fn wait_confirmations(
+    settings: &Settings,
+    txid: &str,
+    confirmations: u64,
+    interval: u64,
+) -> anyhow::Result<()> {
+    let btc_api = settings.btc_api();
     loop {
-        match rpc_api.get_tx(txid) {
-            Ok(tx) => {
-                if tx.confirmations.unwrap_or(0) > 0 {
-                    log::info!("[{}] Tx has confirmed", tx.txid);
-                    audio::play_confirmed();
-                    return Ok(true);
-                }
-            }
-            Err(err) => {
-                log::error!("[monitor] {}", err);
-                log::info!("[{}]Tx has replaced", txid);
-                audio::play_replaced();
-                return Ok(false);
+        let tx = btc_api.get_transaction(txid)?;
+        if let Some(block_height) = tx.status.block_height {
+            let height = btc_api.get_latest_block_height()?;
+            let confirmed = (height + 1).saturating_sub(block_height);
+            if confirmed >= confirmations {
+                log::info!("[{}] Tx has {} confirmations", txid, confirmed);
+                return Ok(());
             }
+            log::info!("[{}] Confirmations: {}/{}", txid, confirmed, confirmations);
+        } else {
+            log::info!("[{}] Waiting for confirmation", txid);
         }
-        log::info!("[monitor] Running...");
         thread::sleep(Duration::from_secs(interval));
     }
-    Ok(false)
 }
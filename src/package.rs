@@ -0,0 +1,142 @@
+use std::collections::{HashSet, VecDeque};
+
+use bitcoin::{Amount, FeeRate, Txid, Weight};
+
+use crate::btc_api::esplora;
+
+/// Unconfirmed txs a new tx depends on, miners only take them together.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct Package {
+    pub(crate) txids: Vec<Txid>,
+    pub(crate) fee: u64,
+    pub(crate) vsize: u64,
+}
+
+impl Package {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.txids.is_empty()
+    }
+
+    pub(crate) fn fee(&self) -> Amount {
+        Amount::from_sat(self.fee)
+    }
+
+    pub(crate) fn fee_rate(&self) -> f64 {
+        if self.vsize == 0 {
+            return 0f64;
+        }
+        self.fee as f64 / self.vsize as f64
+    }
+
+    /// Fee a child of `child_vsize` has to pay so the whole package (ancestors + child)
+    /// reaches `fee_rate`, never lower than the child's own `fee_rate`.
+    pub(crate) fn child_fee(&self, child_vsize: u64, fee_rate: FeeRate) -> Amount {
+        let package_fee = fee_rate
+            .fee_vb(self.vsize + child_vsize)
+            .unwrap()
+            .checked_sub(self.fee())
+            .unwrap_or(Amount::ZERO);
+        package_fee.max(fee_rate.fee_vb(child_vsize).unwrap())
+    }
+
+    /// `child_fee` as a rate for builders sizing the fee by rate, `child_vsize` is the
+    /// smallest the child can be so the rate never falls short.
+    pub(crate) fn child_fee_rate(&self, child_vsize: u64, fee_rate: FeeRate) -> FeeRate {
+        if self.is_empty() || child_vsize == 0 {
+            return fee_rate;
+        }
+        let fee = self.child_fee(child_vsize, fee_rate).to_sat();
+        FeeRate::from_sat_per_vb((fee + child_vsize - 1) / child_vsize).unwrap()
+    }
+}
+
+/// Walk `txids` and all their unconfirmed ancestors.
+pub(crate) fn ancestor_package(
+    btc_api: &esplora::Client,
+    txids: &[Txid],
+) -> anyhow::Result<Package> {
+    let mut package = Package::default();
+    let mut visited = HashSet::new();
+    let mut queue = txids.iter().copied().collect::<VecDeque<_>>();
+
+    while let Some(txid) = queue.pop_front() {
+        if !visited.insert(txid) {
+            continue;
+        }
+        let tx = btc_api.get_transaction(&txid.to_string())?;
+        if tx.status.confirmed {
+            continue;
+        }
+        package.txids.push(txid);
+        package.fee += tx.fee;
+        package.vsize += Weight::from_wu(tx.weight).to_vbytes_ceil();
+
+        for vin in tx.vin.iter().filter(|e| !e.is_coinbase) {
+            queue.push_back(vin.txid);
+        }
+    }
+
+    if !package.is_empty() {
+        log::info!(
+            "[package] Unconfirmed ancestors: {}, TotalFee: {} sat, FeeRate: {:.1} sat/vb, Size: {} vb",
+            package.txids.len(),
+            package.fee,
+            package.fee_rate(),
+            package.vsize
+        );
+    }
+    Ok(package)
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::hashes::Hash;
+
+    use super::*;
+
+    #[test]
+    fn test_child_fee() {
+        let package = Package {
+            txids: vec![],
+            fee: 1000,
+            vsize: 200,
+        };
+        let fee_rate = FeeRate::from_sat_per_vb(20).unwrap();
+        // (200 + 100) * 20 - 1000
+        assert_eq!(package.child_fee(100, fee_rate), Amount::from_sat(5000));
+
+        let package = Package {
+            txids: vec![],
+            fee: 10000,
+            vsize: 200,
+        };
+        // ancestors pay enough, the child pays its own size
+        assert_eq!(package.child_fee(100, fee_rate), Amount::from_sat(2000));
+
+        assert_eq!(
+            Package::default().child_fee(100, fee_rate),
+            Amount::from_sat(2000)
+        );
+    }
+
+    #[test]
+    fn test_child_fee_rate() {
+        let fee_rate = FeeRate::from_sat_per_vb(20).unwrap();
+        let package = Package {
+            txids: vec![Txid::all_zeros()],
+            fee: 1000,
+            vsize: 200,
+        };
+        // 5000 sat for 100 vb
+        assert_eq!(
+            package.child_fee_rate(100, fee_rate),
+            FeeRate::from_sat_per_vb(50).unwrap()
+        );
+        // 5800 sat for 140 vb, rounded up
+        assert_eq!(
+            package.child_fee_rate(140, fee_rate),
+            FeeRate::from_sat_per_vb(42).unwrap()
+        );
+        assert_eq!(Package::default().child_fee_rate(100, fee_rate), fee_rate);
+    }
+}
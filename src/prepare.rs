@@ -1,23 +1,83 @@
-use bitcoin::FeeRate;
+use anyhow::{anyhow, bail};
+use bitcoin::{consensus::encode, Address, Amount, FeeRate};
 
-use crate::{constant::DUMMY_UTXO, send, setting::Settings};
+use crate::{
+    constant::DUMMY_UTXO,
+    send,
+    setting::Settings,
+    snipe::get_wallet_utxos,
+    utils::{export_psbt, print_table, select_confirm},
+};
 
-pub(crate) fn prepare(settings: Settings, fee_rate: u64, number: u64) -> anyhow::Result<()> {
-    let mut wallet = settings.wallet()?;
-    let btc_api = settings.btc_api();
+pub fn prepare(
+    settings: Settings,
+    fee_rate: u64,
+    number: u64,
+    amounts: Vec<u64>,
+    broadcast: bool,
+) -> anyhow::Result<()> {
+    let wallet = settings.wallet()?;
     let pay_addr = wallet.pay_addr();
-    let fee_rate = FeeRate::from_sat_per_vb_unchecked(fee_rate);
-    let utxos = btc_api.get_utxo(&pay_addr.to_string())?;
+    let fee_rate = FeeRate::from_sat_per_vb(fee_rate).ok_or(anyhow!("fee_rate is invalid"))?;
+    let (utxos, _) = get_wallet_utxos(&settings, wallet.as_ref())?;
 
-    let psbt = send::build_psbt(
-        pay_addr.clone(),
-        vec![(pay_addr.clone(), DUMMY_UTXO); number as usize],
+    let amounts = output_amounts(&pay_addr, number, amounts)?;
+    let mut psbt = send::build_psbt(
+        wallet.change_addr(),
+        amounts
+            .into_iter()
+            .map(|amount| (pay_addr.clone(), amount))
+            .collect(),
         fee_rate,
         utxos,
+        &settings.coin_selection,
     )?;
 
+    if wallet.is_watch_only() {
+        return export_psbt(wallet.as_ref(), &psbt, settings.network);
+    }
+    let ok = wallet.sign(&mut psbt)?;
+    if !ok {
+        bail!("Sign failed")
+    }
+    let fee = psbt.fee()?;
+    let signed_tx = psbt.clone().extract_tx()?;
+    let hex = encode::serialize_hex(&signed_tx);
+
+    log::info!(
+        "[{}] FeeRate: {:.1} sat/vb , TotalFee: {} sat , Size: {} vb",
+        signed_tx.txid(),
+        fee.to_sat() as f64 / signed_tx.vsize() as f64,
+        fee.to_sat(),
+        signed_tx.vsize()
+    );
+    print_table(&psbt, settings.network);
+
+    if broadcast {
+        if select_confirm("Please confirm") {
+            settings.broadcast(&hex)?;
+        }
+    }
     Ok(())
 }
+
+/// One amount for every output, or exactly one amount per output.
+fn output_amounts(addr: &Address, number: u64, amounts: Vec<u64>) -> anyhow::Result<Vec<Amount>> {
+    let amounts = match amounts.len() {
+        0 => vec![DUMMY_UTXO; number as usize],
+        1 => vec![Amount::from_sat(amounts[0]); number as usize],
+        len if len as u64 == number => amounts.into_iter().map(Amount::from_sat).collect(),
+        len => bail!("Expect 1 or {} amounts, got {}", number, len),
+    };
+    if amounts.is_empty() {
+        bail!("Number must be greater than 0")
+    }
+    let dust = addr.script_pubkey().dust_value();
+    if let Some(amount) = amounts.iter().find(|e| **e < dust) {
+        bail!("Amount {} is less than dust {}", amount, dust)
+    }
+    Ok(amounts)
+}
 //
 // fn build_psbt(
 //     utxos: Vec<Utxo>,
@@ -80,3 +140,42 @@ pub(crate) fn prepare(settings: Settings, fee_rate: u64, number: u64) -> anyhow:
 //
 //     Ok(())
 // }
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn test_output_amounts() {
+        // taproot, dust 330
+        let addr =
+            Address::from_str("bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr")
+                .unwrap()
+                .assume_checked();
+        let sats = |e: &[u64]| e.iter().map(|e| Amount::from_sat(*e)).collect::<Vec<_>>();
+
+        assert_eq!(
+            output_amounts(&addr, 2, vec![]).unwrap(),
+            vec![DUMMY_UTXO; 2]
+        );
+        assert_eq!(
+            output_amounts(&addr, 2, vec![1000]).unwrap(),
+            sats(&[1000, 1000])
+        );
+        assert_eq!(
+            output_amounts(&addr, 2, vec![1000, 2000]).unwrap(),
+            sats(&[1000, 2000])
+        );
+        assert_eq!(output_amounts(&addr, 1, vec![330]).unwrap(), sats(&[330]));
+
+        // count mismatch
+        assert!(output_amounts(&addr, 3, vec![1000, 2000]).is_err());
+        assert!(output_amounts(&addr, 0, vec![]).is_err());
+        assert!(output_amounts(&addr, 0, vec![1000]).is_err());
+        // dust
+        assert!(output_amounts(&addr, 1, vec![329]).is_err());
+        assert!(output_amounts(&addr, 2, vec![1000, 100]).is_err());
+    }
+}
@@ -2,11 +2,11 @@
 
 use std::collections::{HashMap, VecDeque};
 
-use anyhow::{bail, Error};
+use anyhow::{anyhow, bail, Error};
 use bitcoin::{
     constants::MAX_SCRIPT_ELEMENT_SIZE, opcodes, script, script::Instruction, ScriptBuf,
 };
-use ordinals::{varint, Edict, RuneId};
+use ordinals::{varint, Edict, Rune, RuneId};
 
 #[derive(Copy, Clone, Debug)]
 pub(super) enum Tag {
@@ -241,8 +241,13 @@ pub(crate) fn extract_rune_mint(script_buf: ScriptBuf) -> Result<Option<RuneId>,
     Ok(mint)
 }
 
-pub(crate) fn build_edict_script_buf(mut edicts: Vec<Edict>) -> ScriptBuf {
+/// Runestone of `edicts`, unallocated runes go to `pointer` or the first non OP_RETURN output
+pub(crate) fn build_edict_script_buf(mut edicts: Vec<Edict>, pointer: Option<u32>) -> ScriptBuf {
     let mut payload = Vec::new();
+    if let Some(pointer) = pointer {
+        varint::encode_to_vec(Tag::Pointer.into(), &mut payload);
+        varint::encode_to_vec(pointer.into(), &mut payload);
+    }
     varint::encode_to_vec(Tag::Body.into(), &mut payload);
     edicts.sort_by_key(|edict| edict.id);
     let mut previous = RuneId::default();
@@ -267,6 +272,43 @@ pub(crate) fn build_edict_script_buf(mut edicts: Vec<Edict>) -> ScriptBuf {
     builder.into_script()
 }
 
+/// Rune name committed in the reveal input's tapscript, little endian without trailing zeros
+pub(crate) fn rune_commitment(rune: Rune) -> Vec<u8> {
+    let bytes = rune.0.to_le_bytes();
+    let end = bytes.iter().rposition(|e| *e != 0).map_or(0, |e| e + 1);
+    bytes[..end].to_vec()
+}
+
+/// "1.5" with divisibility 2 is 150
+pub(crate) fn parse_rune_amount(amount: &str, divisibility: u32) -> anyhow::Result<u128> {
+    let (integer, decimal) = amount.split_once('.').unwrap_or((amount, ""));
+    if decimal.len() > divisibility as usize {
+        bail!(
+            "Rune amount {} has more than {} decimals",
+            amount,
+            divisibility
+        );
+    }
+    let integer = if integer.is_empty() {
+        0
+    } else {
+        integer.parse::<u128>()?
+    };
+    let decimal = if decimal.is_empty() {
+        0
+    } else {
+        decimal.parse::<u128>()? * 10u128.pow(divisibility - decimal.len() as u32)
+    };
+    let raw = integer
+        .checked_mul(10u128.pow(divisibility))
+        .and_then(|e| e.checked_add(decimal))
+        .ok_or(anyhow!("Rune amount {} overflow", amount))?;
+    if raw == 0 {
+        bail!("Rune amount must be greater than 0");
+    }
+    Ok(raw)
+}
+
 #[cfg(test)]
 mod tests {
     use ordinals::Runestone;
@@ -278,4 +320,51 @@ mod tests {
         let id = extract_rune_mint(ScriptBuf::from_hex("6a5d071499df3314f20a").unwrap()).unwrap();
         println!("{:?}", id);
     }
+
+    #[test]
+    fn test_rune_commitment() {
+        assert_eq!(rune_commitment(Rune(0)), Vec::<u8>::new());
+        assert_eq!(rune_commitment(Rune(0x0102)), vec![0x02, 0x01]);
+    }
+
+    #[test]
+    fn test_parse_rune_amount() {
+        assert_eq!(parse_rune_amount("1.5", 2).unwrap(), 150);
+        assert_eq!(parse_rune_amount("100", 0).unwrap(), 100);
+        assert_eq!(parse_rune_amount("0.00001", 5).unwrap(), 1);
+        assert_eq!(parse_rune_amount(".5", 1).unwrap(), 5);
+        assert!(parse_rune_amount("1.234", 2).is_err());
+        assert!(parse_rune_amount("1.5", 0).is_err());
+        assert!(parse_rune_amount("0", 2).is_err());
+        assert!(parse_rune_amount("abc", 2).is_err());
+    }
+
+    #[test]
+    fn test_build_edict_script_buf() {
+        let edicts = vec![
+            Edict {
+                id: RuneId::new(840000, 3).unwrap(),
+                amount: 100,
+                output: 2,
+            },
+            Edict {
+                id: RuneId::new(840000, 1).unwrap(),
+                amount: 5,
+                output: 1,
+            },
+        ];
+        for pointer in [None, Some(0)] {
+            let runestone = Runestone {
+                edicts: edicts.clone(),
+                etching: None,
+                mint: None,
+                pointer,
+            }
+            .encipher();
+            assert_eq!(
+                build_edict_script_buf(edicts.clone(), pointer),
+                ScriptBuf::from_bytes(runestone.into_bytes())
+            );
+        }
+    }
 }
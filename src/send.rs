@@ -1,43 +1,119 @@
-use std::str::FromStr;
+use std::{path::PathBuf, str::FromStr};
 
 use anyhow::{anyhow, bail};
 use bitcoin::{
     absolute::LockTime, consensus::encode, psbt::Input, transaction::Version, Address, Amount,
     FeeRate, OutPoint, Psbt, Sequence, Transaction, TxIn, TxOut,
 };
+use ordinals::{Edict, RuneId};
 
 use crate::{
-    btc_api::esplora::Utxo, default, dummy_transaction::DummyTransaction, setting::Settings, snipe,
-    utils::select_confirm,
+    batch::{print_batch_table, read_batch},
+    btc_api::esplora::Utxo,
+    coin_select::{Change, CoinSelector, SelectionTarget},
+    constant::POSTAGE,
+    default,
+    dummy_transaction::DummyTransaction,
+    rune::{build_edict_script_buf, parse_rune_amount},
+    setting::Settings,
+    snipe,
+    utils::{export_psbt, print_table, select_confirm},
+    wallet::Wallet,
 };
 
+pub enum Asset {
+    Btc(f64),
+    /// csv or json of address and btc amount
+    Batch(PathBuf),
+    Inscription(String),
+    Rune {
+        name: String,
+        amount: String,
+    },
+}
+
 pub fn send(
     settings: Settings,
-    to_addr: &str,
-    amount: f64,
+    wallet: &dyn Wallet,
+    to_addr: Option<String>,
+    asset: Asset,
     fee_rate: u64,
     broadcast: bool,
 ) -> anyhow::Result<()> {
-    let wallet = settings.wallet()?;
-    let pay_addr = wallet.pay_addr();
-    let btc_api = settings.btc_api();
+    let ordi_addr = wallet.ordi_addr();
+    let change_addr = wallet.change_addr();
     let fee_rate = FeeRate::from_sat_per_vb(fee_rate).ok_or(anyhow!("fee_rate is invalid"))?;
-    let (utxos, _) = snipe::get_utxos(&btc_api, &pay_addr.to_string())?;
+    let (utxos, _) = snipe::get_wallet_utxos(&settings, wallet)?;
 
-    let to_addr = Address::from_str(to_addr)?.require_network(settings.network)?;
-    let mut psbt = build_psbt(
-        pay_addr.clone(),
-        vec![(to_addr, Amount::from_btc(amount)?)],
-        fee_rate,
-        utxos,
-    )?;
-    let ok = wallet.sign(&mut psbt)?;
+    let to_addr = || -> anyhow::Result<Address> {
+        let to_addr = to_addr
+            .as_ref()
+            .ok_or(anyhow!("Please set the receive address"))?;
+        Ok(Address::from_str(to_addr)?.require_network(settings.network)?)
+    };
+    let mut psbt = match asset {
+        Asset::Btc(amount) => build_psbt(
+            change_addr.clone(),
+            vec![(to_addr()?, Amount::from_btc(amount)?)],
+            fee_rate,
+            utxos,
+            &settings.coin_selection,
+        )?,
+        Asset::Batch(path) => {
+            let recipients = read_batch(&path, settings.network)?;
+            print_batch_table(&recipients);
+            build_psbt(
+                change_addr.clone(),
+                recipients,
+                fee_rate,
+                utxos,
+                &settings.coin_selection,
+            )?
+        }
+        Asset::Inscription(id) => {
+            let (inputs, outputs) = inscription_transfer(&settings, &ordi_addr, &id, to_addr()?)?;
+            build_psbt_with_inputs(
+                change_addr.clone(),
+                inputs,
+                outputs,
+                fee_rate,
+                utxos,
+                &settings.coin_selection,
+            )?
+        }
+        Asset::Rune { name, amount } => {
+            let (inputs, outputs) =
+                rune_transfer(&settings, &ordi_addr, &name, &amount, to_addr()?)?;
+            build_psbt_with_inputs(
+                change_addr.clone(),
+                inputs,
+                outputs,
+                fee_rate,
+                utxos,
+                &settings.coin_selection,
+            )?
+        }
+    };
+    if wallet.is_watch_only() {
+        return export_psbt(wallet, &psbt, settings.network);
+    }
+    let ok = wallet.sign_all(&mut psbt)?;
     if !ok {
         bail!("Sign failed")
     }
-    let signed_tx = psbt.extract_tx()?;
+    let fee = psbt.fee()?;
+    let signed_tx = psbt.clone().extract_tx()?;
     let hex = encode::serialize_hex(&signed_tx);
 
+    log::info!(
+        "[{}] FeeRate: {:.1} sat/vb , TotalFee: {} sat , Size: {} vb",
+        signed_tx.txid(),
+        fee.to_sat() as f64 / signed_tx.vsize() as f64,
+        fee.to_sat(),
+        signed_tx.vsize()
+    );
+    print_table(&psbt, settings.network);
+
     if broadcast {
         let selected = select_confirm("Please confirm");
         if selected {
@@ -48,12 +124,214 @@ pub fn send(
     Ok(())
 }
 
+/// Output 0 takes the inscription with postage, see `inscription_outputs`. Back to `ordi_addr`
+/// the whole utxo stays in output 0 and the sat keeps its offset.
+pub(crate) fn inscription_transfer(
+    settings: &Settings,
+    ordi_addr: &Address,
+    id: &str,
+    to_addr: Address,
+) -> anyhow::Result<(Vec<(Address, OutPoint, Amount)>, Vec<TxOut>)> {
+    let ordi_api = settings.ordi_api();
+
+    let inscription = ordi_api.fetch_inscription(id)?;
+    if inscription.address != Some(ordi_addr.to_string()) {
+        bail!("Inscription {} is not on {}", id, ordi_addr);
+    }
+    let (out_point, offset) = inscription.satpoint()?;
+    let output = ordi_api.output(&out_point)?;
+    if output.spent {
+        bail!("Output {} of inscription {} is spent", out_point, id);
+    }
+    if output.inscriptions.len() > 1 || !output.runes.is_empty() {
+        bail!(
+            "Output {} carries other inscriptions or runes, split it first",
+            out_point
+        );
+    }
+    log::info!(
+        "[inscription] {} at {}:{}, Postage: {}",
+        id,
+        out_point,
+        offset,
+        output.value
+    );
+
+    let outputs = if to_addr == *ordi_addr {
+        vec![TxOut {
+            value: output.value,
+            script_pubkey: to_addr.script_pubkey(),
+        }]
+    } else {
+        inscription_outputs(output.value, offset, ordi_addr, &to_addr)?
+    };
+    Ok((vec![(ordi_addr.clone(), out_point, output.value)], outputs))
+}
+
+/// `to_addr` gets the first `POSTAGE` sats of the utxo, the inscription sat is the first one,
+/// the rest goes back to `ordi_addr` unless it is dust.
+fn inscription_outputs(
+    value: Amount,
+    offset: u64,
+    ordi_addr: &Address,
+    to_addr: &Address,
+) -> anyhow::Result<Vec<TxOut>> {
+    if offset > 0 {
+        bail!(
+            "Inscription is at offset {} of its output, split the sats before it first",
+            offset
+        );
+    }
+    let mut outputs = vec![TxOut {
+        value: POSTAGE.min(value),
+        script_pubkey: to_addr.script_pubkey(),
+    }];
+    let rest = value.checked_sub(POSTAGE).unwrap_or(Amount::ZERO);
+    if rest >= ordi_addr.script_pubkey().dust_value() {
+        outputs.push(TxOut {
+            value: rest,
+            script_pubkey: ordi_addr.script_pubkey(),
+        });
+    } else {
+        outputs[0].value = value;
+    }
+    Ok(outputs)
+}
+
+/// Output 0 takes back the unallocated runes, output 1 receives the edict.
+/// Spending the whole balance of utxos carrying nothing else needs no runestone.
+fn rune_transfer(
+    settings: &Settings,
+    ordi_addr: &Address,
+    name: &str,
+    amount: &str,
+    to_addr: Address,
+) -> anyhow::Result<(Vec<(Address, OutPoint, Amount)>, Vec<TxOut>)> {
+    let ordi_api = settings.ordi_api();
+
+    let rune = ordi_api.fetch_rune_id(name)?;
+    let rune_id = RuneId::from_str(&rune.id)?;
+    let amount = parse_rune_amount(amount, rune.entry.divisibility)?;
+    let (inputs, balance, only_rune) =
+        gather_rune_inputs(settings, ordi_addr, &rune.entry.spaced_rune, amount)?;
+
+    if balance == amount && only_rune {
+        return Ok((
+            inputs,
+            vec![TxOut {
+                value: POSTAGE,
+                script_pubkey: to_addr.script_pubkey(),
+            }],
+        ));
+    }
+
+    let outputs = vec![
+        // rune change
+        TxOut {
+            value: POSTAGE,
+            script_pubkey: ordi_addr.script_pubkey(),
+        },
+        TxOut {
+            value: POSTAGE,
+            script_pubkey: to_addr.script_pubkey(),
+        },
+        TxOut {
+            value: Amount::ZERO,
+            script_pubkey: build_edict_script_buf(
+                vec![Edict {
+                    id: rune_id,
+                    amount,
+                    output: 1,
+                }],
+                None,
+            ),
+        },
+    ];
+    Ok((inputs, outputs))
+}
+
+/// Rune utxos of `ordi_addr` covering `amount`, utxos carrying inscriptions are never spent.
+/// Returns (inputs, balance, only this rune on the inputs).
+pub(crate) fn gather_rune_inputs(
+    settings: &Settings,
+    ordi_addr: &Address,
+    spaced_rune: &str,
+    amount: u128,
+) -> anyhow::Result<(Vec<(Address, OutPoint, Amount)>, u128, bool)> {
+    let btc_api = settings.btc_api();
+    let ordi_api = settings.ordi_api();
+
+    let mut utxos = btc_api.get_utxo(&ordi_addr.to_string())?;
+    utxos.sort_by(|a, b| b.value.cmp(&a.value));
+
+    let mut inputs = Vec::new();
+    let mut balance = 0u128;
+    let mut only_rune = true;
+    for utxo in utxos.into_iter().filter(|e| e.status.confirmed) {
+        let out_point = OutPoint {
+            txid: utxo.txid,
+            vout: utxo.vout,
+        };
+        let output = ordi_api.output(&out_point)?;
+        let rune_amount = output.rune_amount(spaced_rune);
+        if rune_amount == 0 {
+            continue;
+        }
+        if !output.inscriptions.is_empty() {
+            log::info!("[rune] {} skipped, carrying inscriptions", out_point);
+            continue;
+        }
+        only_rune &= output.runes.len() == 1;
+        inputs.push((ordi_addr.clone(), out_point, utxo.value));
+        balance += rune_amount;
+        if balance >= amount {
+            break;
+        }
+    }
+    if balance < amount {
+        bail!(
+            "Rune {} not enough, balance: {}, need: {}",
+            spaced_rune,
+            balance,
+            amount
+        );
+    }
+    log::info!(
+        "[rune] {} inputs: {}, Balance: {}, Send: {}",
+        spaced_rune,
+        inputs.len(),
+        balance,
+        amount
+    );
+    Ok((inputs, balance, only_rune))
+}
+
 pub(crate) fn build_psbt(
     from_addr: Address,
     to_addr_with_amount: Vec<(Address, Amount)>,
     fee_rate: FeeRate,
     utxos: Vec<Utxo>,
-    // pre_utxos: Option<Vec<Utxo>>,
+    coin_selector: &dyn CoinSelector,
+) -> anyhow::Result<Psbt> {
+    let outputs = to_addr_with_amount
+        .into_iter()
+        .map(|(to_addr, amount)| TxOut {
+            value: amount,
+            script_pubkey: to_addr.script_pubkey(),
+        })
+        .collect();
+    build_psbt_with_inputs(from_addr, vec![], outputs, fee_rate, utxos, coin_selector)
+}
+
+/// `inputs` and `outputs` are kept in order at the front, utxos of `from_addr` pay the rest
+/// and the change goes back to `from_addr`.
+pub(crate) fn build_psbt_with_inputs(
+    from_addr: Address,
+    inputs: Vec<(Address, OutPoint, Amount)>,
+    outputs: Vec<TxOut>,
+    fee_rate: FeeRate,
+    utxos: Vec<Utxo>,
+    coin_selector: &dyn CoinSelector,
 ) -> anyhow::Result<Psbt> {
     let mut unsigned_tx = Transaction {
         version: Version::TWO,
@@ -62,15 +340,32 @@ pub(crate) fn build_psbt(
         output: vec![],
     };
     let mut dummy_tx = DummyTransaction::new();
+    let mut psbt_inputs = Vec::new();
+    let mut amount = Amount::ZERO;
+    for (addr, out_point, value) in inputs {
+        unsigned_tx.input.push(TxIn {
+            previous_output: out_point,
+            script_sig: Default::default(),
+            sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+            witness: Default::default(),
+        });
+        dummy_tx.append_input(addr.clone(), None, None);
+        psbt_inputs.push(Input {
+            witness_utxo: Some(TxOut {
+                value,
+                script_pubkey: addr.script_pubkey(),
+            }),
+            ..default()
+        });
+        amount += value;
+    }
+
     let mut need_amount = Amount::ZERO;
     // transfer
-    for (to_addr, amount) in to_addr_with_amount {
-        unsigned_tx.output.push(TxOut {
-            value: amount,
-            script_pubkey: to_addr.script_pubkey(),
-        });
-        dummy_tx.append_output(to_addr.script_pubkey());
-        need_amount += amount;
+    for output in outputs {
+        dummy_tx.append_output(output.script_pubkey.clone());
+        need_amount += output.value;
+        unsigned_tx.output.push(output);
     }
     // change
     unsigned_tx.output.push(TxOut {
@@ -79,10 +374,32 @@ pub(crate) fn build_psbt(
     });
     dummy_tx.append_output(from_addr.script_pubkey());
 
-    let mut amount = Amount::ZERO;
-    let mut ok = false;
-    let mut psbt_inputs = Vec::new();
-    for utxo in utxos {
+    let target = SelectionTarget::new(
+        need_amount.checked_sub(amount).unwrap_or(Amount::ZERO),
+        fee_rate,
+        &dummy_tx,
+        &from_addr,
+    );
+    let mut utxos = coin_selector.select(utxos, &target).into_iter();
+
+    loop {
+        let network_fee = fee_rate.fee_vb(dummy_tx.vsize() as u64).unwrap();
+
+        if let Some(change) = Change::settle(
+            amount,
+            need_amount,
+            network_fee,
+            target.change_fee(),
+            target.dust,
+        ) {
+            let index = unsigned_tx.output.len() - 1;
+            change.apply(&mut unsigned_tx.output, index);
+            break;
+        }
+
+        let Some(utxo) = utxos.next() else {
+            bail!("No utxo or not enough")
+        };
         unsigned_tx.input.push(TxIn {
             previous_output: OutPoint {
                 txid: utxo.txid,
@@ -92,30 +409,19 @@ pub(crate) fn build_psbt(
             sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
             witness: Default::default(),
         });
-        dummy_tx.append_input(from_addr.clone(), None, None);
+        dummy_tx.append_input(utxo.addr_or(&from_addr), None, None);
         psbt_inputs.push(Input {
             witness_utxo: Some({
                 TxOut {
                     value: utxo.value,
-                    script_pubkey: from_addr.script_pubkey(),
+                    script_pubkey: utxo.addr_or(&from_addr).script_pubkey(),
                 }
             }),
             ..default()
         });
         amount += utxo.value;
-
-        let network_fee = fee_rate.fee_vb(dummy_tx.vsize() as u64).unwrap();
-
-        if let Some(unfilled) = amount.checked_sub(network_fee + need_amount) {
-            unsigned_tx.output.last_mut().unwrap().value = unfilled;
-            ok = true;
-            break;
-        }
     }
 
-    if !ok {
-        bail!("No utxo or not enough")
-    }
     let o_len = unsigned_tx.output.len();
     let psbt = Psbt {
         unsigned_tx,
@@ -128,3 +434,29 @@ pub(crate) fn build_psbt(
     };
     Ok(psbt)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inscription_outputs() {
+        let ordi_addr =
+            Address::from_str("bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr")
+                .unwrap()
+                .assume_checked();
+        let to_addr = Address::from_str("bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu")
+            .unwrap()
+            .assume_checked();
+        let values = |value, offset| {
+            inscription_outputs(Amount::from_sat(value), offset, &ordi_addr, &to_addr)
+                .map(|e| e.iter().map(|e| e.value.to_sat()).collect::<Vec<_>>())
+        };
+        assert_eq!(values(50_000, 0).unwrap(), vec![546, 49_454]);
+        assert_eq!(values(546, 0).unwrap(), vec![546]);
+        assert_eq!(values(330, 0).unwrap(), vec![330]);
+        // 254 left is dust for p2tr
+        assert_eq!(values(800, 0).unwrap(), vec![800]);
+        assert!(values(50_000, 1).is_err());
+    }
+}
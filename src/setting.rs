@@ -7,13 +7,19 @@ use bdk::{
     KeychainKind,
 };
 use bip39::Mnemonic;
-use miniscript::{bitcoin::Network, Tap};
+use miniscript::{
+    bitcoin::{Network, OutPoint},
+    Tap,
+};
 use serde::Deserialize;
 
 use crate::{
     broadcast, btc_api,
     btc_api::ordinal,
-    wallet::{MnemonicWallet, Mode, Wallet},
+    coin_select::CoinSelection,
+    tracker,
+    tracker::TxTracker,
+    wallet::{DescriptorConfig, DescriptorWallet, MnemonicWallet, Mode, PrivateKeyWallet, Wallet},
 };
 
 #[derive(Deserialize)]
@@ -22,23 +28,52 @@ pub struct SettingsSerde {
     private_key: Option<String>,
     mnemonic: Option<String>,
     wallet_mode: Mode,
+    /// Address index or bip32 account of wallet_mode
+    #[serde(default)]
+    wallet_account: u32,
     //污点
     poison_mnemonic: Option<String>,
     ordi_api: String,
     rpc_api: Option<String>,
+    /// A descriptor string, watch-only unless it holds private keys, or
+    /// `{ script, account, path }` derived from the mnemonic. Overrides wallet_mode
+    pay_descriptor: Option<DescriptorConfig>,
+    /// Defaults to pay_descriptor
+    ordi_descriptor: Option<DescriptorConfig>,
+    /// Scan pay addresses until this many unused in a row, 0 uses pay_addr only
+    #[serde(default)]
+    gap_limit: u32,
+    /// bitcoind zmqpubrawtx/zmqpubsequence endpoint, used with rpc_api
+    zmq_api: Option<String>,
+    /// mempool websocket, defaults to mempool.space of the network
+    ws_api: Option<String>,
     broadcast_rest_apis: Vec<broadcast::RestApi>,
+    #[serde(default)]
+    coin_selection: CoinSelection,
+    /// Spent even if the indexer reports inscriptions or runes on it
+    #[serde(default)]
+    spendable_outpoints: Vec<OutPoint>,
 }
 
+#[derive(Clone)]
 pub struct Settings {
     // pub(crate) btc_api_addr: Address<NetworkChecked>,
     pub(crate) network: Network,
-    // pub(crate) private_key: Option<String>,
+    private_key: Option<String>,
+    pay_descriptor: Option<DescriptorConfig>,
+    ordi_descriptor: Option<DescriptorConfig>,
     pub(crate) mnemonic: Option<String>,
     pub(crate) poison_mnemonic: Option<String>,
     wallet_mode: Mode,
+    wallet_account: u32,
     ordi_api: String,
     rpc_api: Option<String>,
+    zmq_api: Option<String>,
+    ws_api: Option<String>,
+    gap_limit: u32,
     broadcast_rest_apis: Vec<broadcast::RestApi>,
+    pub(crate) coin_selection: CoinSelection,
+    pub(crate) spendable_outpoints: HashSet<OutPoint>,
 }
 
 impl TryFrom<SettingsSerde> for Settings {
@@ -47,13 +82,21 @@ impl TryFrom<SettingsSerde> for Settings {
     fn try_from(value: SettingsSerde) -> Result<Self, Self::Error> {
         Ok(Self {
             network: value.network,
-            // private_key: value.private_key,
+            private_key: value.private_key,
+            pay_descriptor: value.pay_descriptor,
+            ordi_descriptor: value.ordi_descriptor,
             mnemonic: value.mnemonic,
             poison_mnemonic: value.poison_mnemonic,
             wallet_mode: value.wallet_mode,
+            wallet_account: value.wallet_account,
             ordi_api: value.ordi_api,
             rpc_api: value.rpc_api,
+            zmq_api: value.zmq_api,
+            ws_api: value.ws_api,
+            gap_limit: value.gap_limit,
             broadcast_rest_apis: value.broadcast_rest_apis,
+            coin_selection: value.coin_selection,
+            spendable_outpoints: value.spendable_outpoints.into_iter().collect(),
         })
     }
 }
@@ -70,13 +113,27 @@ pub fn read_settings_from_file(path_buf: PathBuf) -> anyhow::Result<Settings> {
 }
 
 impl Settings {
+    /// Override the config one
+    pub fn with_coin_selection(mut self, coin_selection: Option<CoinSelection>) -> Self {
+        if let Some(coin_selection) = coin_selection {
+            self.coin_selection = coin_selection;
+        }
+        self
+    }
+
     pub fn check(&self) -> anyhow::Result<()> {
         let wallet = self.wallet()?;
         let poison_wallet = self.poison_wallet()?;
         log::info!("[Network] {} ", self.network);
-        log::info!("[Wallet] Mode: {:?} ", self.wallet_mode);
+        log::info!(
+            "[Wallet] Mode: {:?}, Account: {} ",
+            self.wallet_mode,
+            self.wallet_account
+        );
+        log::info!("[Wallet] CoinSelection: {:?} ", self.coin_selection);
         log::info!("[Wallet] Pay: {} ", wallet.pay_addr());
         log::info!("[Wallet] Ordi: {} ", wallet.ordi_addr());
+        log::info!("[Wallet] Change: {} ", wallet.change_addr());
         log::info!("[PoisonWallet] Pay: {} ", poison_wallet.pay_addr());
         log::info!("[PoisonWallet] Ordi: {} ", poison_wallet.ordi_addr());
         Ok(())
@@ -89,6 +146,39 @@ impl Settings {
         ))
     }
 
+    /// Local node zmq if configured, mempool websocket otherwise. Reconnected when dropped,
+    /// esplora is polled meanwhile.
+    pub(crate) fn tx_tracker(&self) -> anyhow::Result<Box<dyn TxTracker>> {
+        let fallback = tracker::poll::Client::new(self.btc_api());
+        #[cfg(not(feature = "zmq"))]
+        if self.zmq_api.as_ref().is_some_and(|e| !e.is_empty()) {
+            log::warn!("[tracker] zmq_api is ignored, build with `--features zmq`");
+        }
+        #[cfg(feature = "zmq")]
+        if let (Some(zmq_api), Some(_)) = (&self.zmq_api, &self.rpc_api) {
+            let settings = self.clone();
+            let zmq_api = zmq_api.clone();
+            return Ok(Box::new(tracker::Reconnect::new(
+                move || {
+                    Ok(Box::new(tracker::zmq::Client::connect(
+                        &zmq_api,
+                        settings.network,
+                        settings.rpc_api(),
+                    )?))
+                },
+                fallback,
+            )?));
+        }
+        let url = match &self.ws_api {
+            Some(url) if !url.is_empty() => url.clone(),
+            _ => tracker::ws::url(self.network)?,
+        };
+        Ok(Box::new(tracker::Reconnect::new(
+            move || Ok(Box::new(tracker::ws::Client::connect(&url)?)),
+            fallback,
+        )?))
+    }
+
     pub(crate) fn btc_api(&self) -> btc_api::esplora::Client {
         btc_api::esplora::new(self.network)
     }
@@ -98,16 +188,47 @@ impl Settings {
         ordinal::Client::new(&self.ordi_api)
     }
 
-    pub(crate) fn wallet(&self) -> anyhow::Result<MnemonicWallet> {
-        if let Some(words) = &self.mnemonic {
-            return Ok(MnemonicWallet::new(words, self.wallet_mode, self.network)?);
+    /// Discovered up to gap_limit if set
+    pub fn wallet(&self) -> anyhow::Result<Box<dyn Wallet>> {
+        let mut wallet = self.new_wallet()?;
+        if self.gap_limit > 0 {
+            wallet.discover(&self.btc_api(), self.gap_limit)?;
+        }
+        Ok(wallet)
+    }
+
+    /// pay_descriptor, then mnemonic, then private_key
+    fn new_wallet(&self) -> anyhow::Result<Box<dyn Wallet>> {
+        let mnemonic = self.mnemonic.as_deref().filter(|e| !e.is_empty());
+        if let Some(pay) = &self.pay_descriptor {
+            let pay = pay.descriptor(mnemonic, self.network)?;
+            let ordi = match &self.ordi_descriptor {
+                Some(ordi) => Some(ordi.descriptor(mnemonic, self.network)?),
+                None => None,
+            };
+            return Ok(Box::new(DescriptorWallet::new(
+                &pay,
+                ordi.as_deref(),
+                self.network,
+            )?));
+        }
+        if let Some(words) = mnemonic {
+            return Ok(Box::new(MnemonicWallet::new(
+                words,
+                self.wallet_mode,
+                self.wallet_account,
+                self.network,
+            )?));
+        }
+        if let Some(wif) = self.private_key.as_ref().filter(|e| !e.is_empty()) {
+            return Ok(Box::new(PrivateKeyWallet::new(wif, self.network)?));
         }
-        bail!("Please setting [mnemonic]")
+        bail!("Please setting [mnemonic], [private_key] or [pay_descriptor]")
     }
 
     pub(crate) fn poison_wallet(&self) -> anyhow::Result<MnemonicWallet> {
         if let Some(words) = &self.poison_mnemonic {
-            return Ok(MnemonicWallet::new(words, Mode::XVerse, self.network)?);
+            return Ok(MnemonicWallet::new(words, Mode::XVerse, 0, self.network)?);
         }
         bail!("Please setting [poison_mnemonic]")
     }
@@ -0,0 +1,270 @@
+use std::fmt;
+
+use bitcoin::{
+    sighash::EcdsaSighashType, Script, ScriptBuf, TapSighashType, Transaction, TxOut, Witness,
+};
+
+use crate::{btc_api::esplora, setting::Settings};
+
+/// Sighash of a signed input, decoded from its witness
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub(crate) enum Sighash {
+    Taproot(TapSighashType),
+    Ecdsa(EcdsaSighashType),
+}
+
+impl Sighash {
+    fn anyone_can_pay(&self) -> bool {
+        match self {
+            Sighash::Taproot(e) => matches!(
+                e,
+                TapSighashType::AllPlusAnyoneCanPay
+                    | TapSighashType::NonePlusAnyoneCanPay
+                    | TapSighashType::SinglePlusAnyoneCanPay
+            ),
+            Sighash::Ecdsa(e) => matches!(
+                e,
+                EcdsaSighashType::AllPlusAnyoneCanPay
+                    | EcdsaSighashType::NonePlusAnyoneCanPay
+                    | EcdsaSighashType::SinglePlusAnyoneCanPay
+            ),
+        }
+    }
+
+    fn is_single(&self) -> bool {
+        match self {
+            Sighash::Taproot(e) => matches!(
+                e,
+                TapSighashType::Single | TapSighashType::SinglePlusAnyoneCanPay
+            ),
+            Sighash::Ecdsa(e) => matches!(
+                e,
+                EcdsaSighashType::Single | EcdsaSighashType::SinglePlusAnyoneCanPay
+            ),
+        }
+    }
+
+    fn is_none(&self) -> bool {
+        match self {
+            Sighash::Taproot(e) => matches!(
+                e,
+                TapSighashType::None | TapSighashType::NonePlusAnyoneCanPay
+            ),
+            Sighash::Ecdsa(e) => matches!(
+                e,
+                EcdsaSighashType::None | EcdsaSighashType::NonePlusAnyoneCanPay
+            ),
+        }
+    }
+
+    /// The signature stays valid in a tx with other inputs and outputs
+    pub(crate) fn liftable(&self) -> bool {
+        self.anyone_can_pay() && (self.is_single() || self.is_none())
+    }
+}
+
+impl fmt::Display for Sighash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Sighash::Taproot(e) => write!(f, "{}", e),
+            Sighash::Ecdsa(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+pub(crate) struct InputSighash {
+    pub(crate) index: usize,
+    /// None for script path and other unknown spends
+    pub(crate) sighash: Option<Sighash>,
+    /// Outputs the signature commits to
+    pub(crate) outputs: Vec<usize>,
+    pub(crate) liftable: bool,
+}
+
+/// Key path and p2wpkh signatures only, script paths are not decoded.
+pub(crate) fn decode_sighash(witness: &Witness, script_pubkey: &Script) -> Option<Sighash> {
+    if script_pubkey.is_p2tr() {
+        // annex is the last element starting with 0x50
+        let len = match witness.last() {
+            Some(last) if witness.len() > 1 && last.first() == Some(&0x50) => witness.len() - 1,
+            _ => witness.len(),
+        };
+        if len != 1 {
+            return None;
+        }
+        let sig = witness.nth(0)?;
+        return match sig.len() {
+            64 => Some(Sighash::Taproot(TapSighashType::Default)),
+            65 => TapSighashType::from_consensus_u8(sig[64])
+                .ok()
+                .map(Sighash::Taproot),
+            _ => None,
+        };
+    }
+    let p2sh_p2wpkh = script_pubkey.is_p2sh() && witness.len() == 2 && witness.nth(1)?.len() == 33;
+    if (script_pubkey.is_p2wpkh() || p2sh_p2wpkh) && witness.len() == 2 {
+        let hash_ty = *witness.nth(0)?.last()?;
+        return Some(Sighash::Ecdsa(EcdsaSighashType::from_consensus(
+            hash_ty as u32,
+        )));
+    }
+    None
+}
+
+/// Sighash of every input of `tx`, `prevouts` in input order
+pub(crate) fn analyze_tx(tx: &Transaction, prevouts: &[TxOut]) -> Vec<InputSighash> {
+    tx.input
+        .iter()
+        .zip(prevouts)
+        .enumerate()
+        .map(|(index, (input, prevout))| {
+            let sighash = decode_sighash(&input.witness, &prevout.script_pubkey);
+            let outputs = match sighash {
+                Some(e) if e.is_single() => vec![index]
+                    .into_iter()
+                    .filter(|i| *i < tx.output.len())
+                    .collect(),
+                Some(e) if e.is_none() => vec![],
+                _ => (0..tx.output.len()).collect(),
+            };
+            // SINGLE without its output signs the `1` hash, nothing to lift
+            let liftable = sighash.is_some_and(|e| e.liftable())
+                && (sighash.is_some_and(|e| e.is_none()) || !outputs.is_empty());
+            InputSighash {
+                index,
+                sighash,
+                outputs,
+                liftable,
+            }
+        })
+        .collect()
+}
+
+pub(crate) fn esplora_prevouts(pool_tx: &esplora::Transaction) -> anyhow::Result<Vec<TxOut>> {
+    pool_tx
+        .vin
+        .iter()
+        .map(|e| {
+            Ok(TxOut {
+                value: e.prevout.value,
+                script_pubkey: ScriptBuf::from_hex(&e.prevout.scriptpubkey)?,
+            })
+        })
+        .collect()
+}
+
+/// Print the sighash of every input of a tx and which ones can be lifted
+pub fn analyze(settings: Settings, txid: &str) -> anyhow::Result<()> {
+    let btc_api = settings.btc_api();
+    let pool_tx = btc_api.get_transaction(txid)?;
+    let tx = btc_api.get_btc_transaction(txid)?;
+    let analysis = analyze_tx(&tx, &esplora_prevouts(&pool_tx)?);
+
+    let mut table = comfy_table::Table::new();
+    table.set_header(vec!["Input", "Outpoint", "Sighash", "Commits", "Liftable"]);
+    for e in &analysis {
+        let commits = match (&e.sighash, e.outputs.len()) {
+            (None, _) => "-".to_string(),
+            (_, 0) => "none".to_string(),
+            (_, n) if n == tx.output.len() && n > 1 => "all".to_string(),
+            _ => e
+                .outputs
+                .iter()
+                .map(|e| e.to_string())
+                .collect::<Vec<_>>()
+                .join(","),
+        };
+        table.add_row(vec![
+            e.index.to_string(),
+            tx.input[e.index].previous_output.to_string(),
+            e.sighash.map_or("unknown".to_string(), |e| e.to_string()),
+            commits,
+            if e.liftable { "yes" } else { "no" }.to_string(),
+        ]);
+    }
+    println!("{}", table);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::{
+        absolute::LockTime, hashes::Hash, transaction::Version, Amount, OutPoint, Sequence, TxIn,
+        WPubkeyHash,
+    };
+
+    use super::*;
+
+    fn tx_out(script_pubkey: ScriptBuf) -> TxOut {
+        TxOut {
+            value: Amount::from_sat(10_000),
+            script_pubkey,
+        }
+    }
+
+    fn tx_in(witness: &[Vec<u8>]) -> TxIn {
+        TxIn {
+            previous_output: OutPoint::null(),
+            script_sig: Default::default(),
+            sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+            witness: Witness::from_slice(witness),
+        }
+    }
+
+    #[test]
+    fn test_analyze_tx() {
+        let p2tr = ScriptBuf::from_hex(
+            "5120a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1",
+        )
+        .unwrap();
+        let p2wpkh = ScriptBuf::new_p2wpkh(&WPubkeyHash::from_byte_array([1; 20]));
+        let mut sig = vec![0; 64];
+        sig.push(0x83);
+        let mut ecdsa_sig = vec![0; 71];
+        ecdsa_sig.push(0x01);
+
+        let tx = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![
+                tx_in(&[sig.clone()]),
+                tx_in(&[vec![0; 64]]),
+                tx_in(&[ecdsa_sig, vec![2; 33]]),
+                tx_in(&[sig]),
+            ],
+            output: vec![
+                tx_out(p2tr.clone()),
+                tx_out(p2tr.clone()),
+                tx_out(p2tr.clone()),
+            ],
+        };
+        let prevouts = vec![
+            tx_out(p2tr.clone()),
+            tx_out(p2tr.clone()),
+            tx_out(p2wpkh),
+            tx_out(p2tr),
+        ];
+        let analysis = analyze_tx(&tx, &prevouts);
+
+        assert_eq!(
+            analysis[0].sighash,
+            Some(Sighash::Taproot(TapSighashType::SinglePlusAnyoneCanPay))
+        );
+        assert_eq!(analysis[0].outputs, vec![0]);
+        assert!(analysis[0].liftable);
+        assert_eq!(
+            analysis[1].sighash,
+            Some(Sighash::Taproot(TapSighashType::Default))
+        );
+        assert_eq!(analysis[1].outputs, vec![0, 1, 2]);
+        assert!(!analysis[1].liftable);
+        assert_eq!(
+            analysis[2].sighash,
+            Some(Sighash::Ecdsa(EcdsaSighashType::All))
+        );
+        assert!(!analysis[2].liftable);
+        // SINGLE without output 3
+        assert!(analysis[3].outputs.is_empty());
+        assert!(!analysis[3].liftable);
+    }
+}
@@ -2,10 +2,7 @@ use std::{str::FromStr, thread, time::Duration};
 
 use anyhow::{anyhow, bail};
 use bdk::psbt::PsbtUtils;
-use bitcoin::{
-    absolute::LockTime, consensus::encode, psbt, psbt::PsbtSighashType, transaction::Version,
-    ScriptBuf, TapSighashType,
-};
+use bitcoin::{absolute::LockTime, consensus::encode, psbt, transaction::Version, ScriptBuf};
 use clap::ValueEnum;
 use console_utils::input::select;
 use log::log;
@@ -15,6 +12,7 @@ use miniscript::bitcoin::{
 use ordinals::{Edict, Runestone};
 
 use crate::{
+    bid::{print_bid_report, BidPolicy, Round},
     btc_api::{
         esplora,
         esplora::{model::Utxo, Prevout},
@@ -22,12 +20,20 @@ use crate::{
         ordinal::Ordinal,
         TxApi,
     },
-    constant::{APPEND_NETWORK_FEE_SAT, DUMMY_UTXO, MIN_UTXO, POSTAGE},
+    coin_select::{Change, CoinSelector, SelectionTarget},
+    constant::{APPEND_NETWORK_FEE_SAT, DUMMY_UTXO, POSTAGE},
     default,
     dummy_transaction::DummyTransaction,
+    list::build_listing_psbt,
     monitor,
+    package::ancestor_package,
     setting::Settings,
-    utils, Print,
+    sighash::{analyze_tx, esplora_prevouts},
+    tracker::TxOutcome,
+    utils,
+    verify::verify_psbt,
+    wallet::Wallet,
+    Print,
 };
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
@@ -37,12 +43,14 @@ pub enum Type {
     Rune,
 }
 
+/// Snipe the listing tx, with `monitor` keep outbidding whoever replaces us within `bid`
 pub fn snipe(
     settings: Settings,
+    wallet: &dyn Wallet,
     tx_id: &str,
     addr: &str,
     typ: Type,
-    increase_rate: u64,
+    bid: BidPolicy,
     broadcast: bool,
     show_hex: bool,
     yes: bool,
@@ -50,13 +58,79 @@ pub fn snipe(
     simple: bool,
     split_rate: Option<u64>,
     split_recv_addr: Option<String>,
-    check: Option<String>,
+    _check: Option<String>,
     monitor: bool,
 ) -> anyhow::Result<()> {
+    let mut rounds: Vec<Round> = Vec::new();
+    let mut tx_id = tx_id.to_string();
+
+    let result = (|| -> anyhow::Result<()> {
+        for _ in 0..bid.max_rounds {
+            let Some(round) = snipe_round(
+                &settings,
+                wallet,
+                &tx_id,
+                addr,
+                typ,
+                &bid,
+                broadcast,
+                show_hex,
+                yes,
+                poison,
+                simple,
+                split_rate,
+                split_recv_addr.clone(),
+            )?
+            else {
+                return Ok(());
+            };
+            tx_id = round.replaced.clone();
+            rounds.push(round);
+            if !monitor {
+                return Ok(());
+            }
+            let round = rounds.last_mut().unwrap();
+            // the replacement comes with the tracker event, no esplora poll
+            match monitor::monitor(&settings, &round.txid, 3)? {
+                TxOutcome::Confirmed => {
+                    round.confirmed = true;
+                    return Ok(());
+                }
+                TxOutcome::Replaced { by } => {
+                    log::info!("[bid] {} outbid by {}", round.txid, by);
+                    round.outbid_by = Some(by);
+                }
+            }
+        }
+        log::info!("[bid] Stop, max rounds {} reached", bid.max_rounds);
+        Ok(())
+    })();
+
+    if !rounds.is_empty() {
+        print_bid_report(&rounds);
+    }
+    result
+}
+
+/// One bid, None when nothing was broadcast
+fn snipe_round(
+    settings: &Settings,
+    wallet: &dyn Wallet,
+    tx_id: &str,
+    addr: &str,
+    typ: Type,
+    bid: &BidPolicy,
+    broadcast: bool,
+    show_hex: bool,
+    yes: bool,
+    poison: bool,
+    simple: bool,
+    split_rate: Option<u64>,
+    split_recv_addr: Option<String>,
+) -> anyhow::Result<Option<Round>> {
     let btc_api = settings.btc_api();
     let ordi_api = settings.ordi_api();
 
-    let wallet = settings.wallet()?;
     let pay_addr = wallet.pay_addr();
     let ordi_addr = wallet.ordi_addr();
     wallet.check();
@@ -98,16 +172,33 @@ pub fn snipe(
         if !yes {
             let selected_index = select("Try snipe new tx ?", &["No", "Yes"]);
             if selected_index == 0 {
-                return Ok(());
+                return Ok(None);
             }
         }
     }
 
-    let (can_utxos, dummy_utxos) = get_utxos(&btc_api, &pay_addr.to_string())?;
+    let (can_utxos, dummy_utxos) = get_wallet_utxos(settings, wallet)?;
 
-    let fee_rate =
-        FeeRate::from_sat_per_vb(snipe_pool_tx.fee / snipe_tx.vsize() as u64 + increase_rate)
-            .unwrap();
+    let origin_rate = snipe_pool_tx.fee / snipe_tx.vsize() as u64;
+    let Some(fee_rate) = bid.next_fee_rate(origin_rate) else {
+        log::info!(
+            "[bid] Stop, outbidding {} sat/vb is over the cap {} sat/vb",
+            origin_rate,
+            bid.max_fee_rate.unwrap_or_default()
+        );
+        return Ok(None);
+    };
+    // the seller's unconfirmed parents are mined together with us, pay for them too
+    let seller_txids = analyze_tx(&snipe_tx, &esplora_prevouts(&snipe_pool_tx)?)
+        .into_iter()
+        .filter(|e| e.liftable)
+        .map(|e| snipe_tx.input[e.index].previous_output.txid)
+        .collect::<Vec<_>>();
+    let package = ancestor_package(&btc_api, &seller_txids)?;
+    let fee_rate = package.child_fee_rate(
+        snipe_tx.vsize() as u64,
+        FeeRate::from_sat_per_vb(fee_rate).unwrap(),
+    );
 
     let (mut unsigned_psbt, ordinals) = if simple {
         build_uncompleted_psbt_without_dummy(
@@ -132,6 +223,7 @@ pub fn snipe(
             can_utxos,
             dummy_utxos,
             pay_addr.clone(),
+            wallet.change_addr(),
             ordi_addr.clone(),
             typ,
             fee_rate,
@@ -142,27 +234,42 @@ pub fn snipe(
     if show_hex {
         log::info!("[unsigned PSBT] {}", unsigned_psbt.serialize_hex());
     }
+    if wallet.is_watch_only() {
+        utils::export_psbt(wallet, &unsigned_psbt, settings.network)?;
+        return Ok(None);
+    }
     let ok = wallet.sign(&mut unsigned_psbt)?;
     if !ok {
         bail!("Sign failed")
     }
 
     let signed_tx = unsigned_psbt.clone().extract_tx()?;
+    // seller signatures copied from the mempool must still commit to our layout
+    verify_psbt(&unsigned_psbt, &signed_tx)?;
     let hex = encode::serialize_hex(&signed_tx);
 
     if show_hex {
         log::info!("[signed] {}", hex);
     }
-    let fee_rate = unsigned_psbt.fee_amount().unwrap_or(0) as f64 / signed_tx.vsize() as f64;
+    let fee = unsigned_psbt.fee_amount().unwrap_or(0);
+    let fee_rate = fee as f64 / signed_tx.vsize() as f64;
 
     log::info!(
         "[{}] FeeRate: {:.1} sat/vb , TotalFee: {} sat , Size: {} vb",
         signed_tx.txid(),
         fee_rate,
-        unsigned_psbt.fee_amount().unwrap_or(0),
+        fee,
         signed_tx.vsize()
     );
     utils::print_snipe_table(&unsigned_psbt, ordinals, settings.network);
+    if bid.max_fee.is_some_and(|e| fee > e) {
+        log::info!(
+            "[bid] Stop, total fee {} sat is over the cap {} sat",
+            fee,
+            bid.max_fee.unwrap()
+        );
+        return Ok(None);
+    }
 
     let split_psbt = if simple {
         let outpoint = OutPoint {
@@ -210,10 +317,14 @@ pub fn snipe(
         None
     };
     if broadcast {
-        let selected = select("Please confirm cost: ", &["No", "Yes"]);
-        if selected == 0 {
-            log::info!("You have canceled the sniper, and the transaction did not take effect.");
-            return Ok(());
+        if !yes {
+            let selected = select("Please confirm cost: ", &["No", "Yes"]);
+            if selected == 0 {
+                log::info!(
+                    "You have canceled the sniper, and the transaction did not take effect."
+                );
+                return Ok(None);
+            }
         }
 
         settings.broadcast(&hex)?;
@@ -227,39 +338,27 @@ pub fn snipe(
             };
         }
 
-        // monitor
-        if monitor {
-            if let ok = monitor::monitor(&settings, &signed_tx.txid().to_string(), 3)? {
-                if !ok {
-                    return snipe(
-                        settings,
-                        &tx_id,
-                        addr,
-                        typ,
-                        increase_rate,
-                        broadcast,
-                        show_hex,
-                        yes,
-                        poison,
-                        simple,
-                        split_rate,
-                        split_recv_addr,
-                        check,
-                        monitor,
-                    );
-                }
-            }
-        }
+        return Ok(Some(Round {
+            txid: signed_tx.txid().to_string(),
+            replaced: tx_id,
+            fee,
+            fee_rate,
+            confirmed: false,
+            outbid_by: None,
+        }));
     }
 
-    Ok(())
+    Ok(None)
 }
 
-/// (available_utxo , dummy_utxo)
-pub(crate) fn get_utxos(
-    btc_api: &esplora::Client,
-    addr: &str,
-) -> anyhow::Result<(Vec<Utxo>, Vec<Utxo>)> {
+/// (available_utxo , dummy_utxo), utxos carrying inscriptions or runes are never returned
+/// unless allowed by `spendable_outpoints`
+pub(crate) fn get_utxos(settings: &Settings, addr: &str) -> anyhow::Result<(Vec<Utxo>, Vec<Utxo>)> {
+    let btc_api = settings.btc_api();
+    let ordi_api = settings.ordi_api();
+
+    let address = Address::from_str(addr)?.require_network(settings.network)?;
+
     // utxos dummy utxo
     let mut utxos = btc_api.get_utxo(addr)?;
     utxos.sort_by(|a, b| b.value.cmp(&a.value));
@@ -268,26 +367,67 @@ pub(crate) fn get_utxos(
         .into_iter()
         .filter(|e| e.status.confirmed)
         .filter(|e| e.value > Amount::from_sat(546))
+        .map(|e| Utxo {
+            address: Some(address.clone()),
+            ..e
+        })
         .collect::<Vec<_>>();
-    // 过滤掉铭文
 
-    let (dummy_utxos, utxos) = utxos
+    // 过滤掉铭文和符文
+    let mut cardinal_utxos = Vec::new();
+    for utxo in utxos {
+        let out_point = OutPoint {
+            txid: utxo.txid,
+            vout: utxo.vout,
+        };
+        if is_spendable(settings, &ordi_api, &out_point)? {
+            cardinal_utxos.push(utxo);
+        }
+    }
+
+    let (dummy_utxos, utxos) = cardinal_utxos
         .into_iter()
-        .filter(|e| {
-            // 筛选 dummy utxo 和 大于 10000sat的 utxo （防止误操作铭文资产） 后续可改api直接查询 okx wass api
-            if e.value == DUMMY_UTXO {
-                return true;
-            }
-            if e.value > MIN_UTXO {
-                return true;
-            }
-            return false;
-        })
         .partition(|e| e.value == DUMMY_UTXO);
 
     Ok((utxos, dummy_utxos))
 }
 
+/// No inscription or rune by the indexer, or allowed by `spendable_outpoints`
+fn is_spendable(
+    settings: &Settings,
+    ordi_api: &ordinal::Client,
+    out_point: &OutPoint,
+) -> anyhow::Result<bool> {
+    if settings.spendable_outpoints.contains(out_point) {
+        log::info!("[utxo] {} allowed by config", out_point);
+        return Ok(true);
+    }
+    if !ordi_api.is_cardinal(out_point)? {
+        log::info!(
+            "[utxo] {} skipped, inscription or rune or not indexed",
+            out_point
+        );
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+/// `get_utxos` of every address of the wallet, largest first
+pub(crate) fn get_wallet_utxos(
+    settings: &Settings,
+    wallet: &dyn Wallet,
+) -> anyhow::Result<(Vec<Utxo>, Vec<Utxo>)> {
+    let mut utxos = Vec::new();
+    let mut dummy_utxos = Vec::new();
+    for addr in wallet.pay_addrs() {
+        let (mut a, mut b) = get_utxos(settings, &addr.to_string())?;
+        utxos.append(&mut a);
+        dummy_utxos.append(&mut b);
+    }
+    utxos.sort_by(|a, b| b.value.cmp(&a.value));
+    Ok((utxos, dummy_utxos))
+}
+
 /// get utxo of fixed value
 pub(crate) fn get_value_utxos(
     btc_api: &esplora::Client,
@@ -378,8 +518,9 @@ fn build_uncompleted_psbt(
     snipe_pool_tx: &esplora::Transaction,
     snipe_tx: &Transaction,
     cardinal_utxos: Vec<Utxo>,
-    mut dummy_utxos: Vec<Utxo>,
+    dummy_utxos: Vec<Utxo>,
     pay_addr: Address,
+    change_addr: Address,
     rev_addr: Address,
     typ: Type,
     fee_rate: FeeRate,
@@ -389,8 +530,6 @@ fn build_uncompleted_psbt(
         bail!("Origin tx confirmed, can not replaced");
     }
 
-    let mut dummy_signed_tx_1 = DummyTransaction::new();
-
     log::info!(
         "[origin tx] TotalFee: {} sat, FeeRate: {:.1} sat/vb, Size: {} vb ",
         snipe_pool_tx.fee,
@@ -398,58 +537,8 @@ fn build_uncompleted_psbt(
         snipe_tx.vsize(),
     );
 
-    let mut inputs = Vec::new();
-    let mut signed_psbt_inputs = Vec::new();
-    let mut inputs_amount = Amount::ZERO;
-
-    let mut outputs = Vec::new();
-    let mut outputs_amount = Amount::ZERO;
-
-    let mut is_rune = typ == Type::Rune;
-    let snipe_utxos = snipe_tx
-        .input
-        .iter()
-        .map(|e| &e.previous_output)
-        .collect::<Vec<_>>();
-
-    log::info!("[waiting] Founding inscription and rune from origin tx");
-    let ordinals = ordi_api.fetch_outputs(snipe_utxos)?;
-
-    let mut ordinal_and_output = ordinals
-        .into_iter()
-        .map(|(i, ordi)| {
-            (
-                i,
-                ordi,
-                (
-                    snipe_tx.input[i].clone(),
-                    snipe_pool_tx.vin[i].prevout.clone(),
-                ),
-                snipe_tx.output[i].clone(),
-            )
-        })
-        .collect::<Vec<_>>();
-
-    if ordinal_and_output.len() < 1 {
-        bail!("Not found inscription or rune");
-    }
-
-    let mut selected = if ordinal_and_output.len() > 1 {
-        let ordinal_options = ordinal_and_output
-            .iter()
-            .map(|(_, ordinal, _, txout)| ordinal.display_value(txout.value))
-            .collect::<Vec<_>>();
-        let selected = console_utils::input::multiselect(
-            "Multiple runes or inscriptions were found. Please select one or more (By SPACE Key)",
-            &ordinal_options
-                .iter()
-                .map(|e| e.as_str())
-                .collect::<Vec<_>>(),
-        );
-        selected
-    } else {
-        vec![true; ordinal_and_output.len()]
-    };
+    let mut ordinal_and_output = find_liftable_ordinals(ordi_api, snipe_pool_tx, snipe_tx)?;
+    let mut selected = vec![true; ordinal_and_output.len()];
 
     if poison {
         // found first selected
@@ -470,6 +559,50 @@ fn build_uncompleted_psbt(
         ordinal_and_output = ordinal_and_output_copy;
     }
 
+    let psbt = build_fill_psbt(
+        settings,
+        &ordinal_and_output,
+        &selected,
+        typ == Type::Rune,
+        cardinal_utxos,
+        dummy_utxos,
+        pay_addr,
+        change_addr,
+        rev_addr,
+        fee_rate,
+        Some(Amount::from_sat(snipe_pool_tx.fee)),
+        (snipe_tx.version, snipe_tx.lock_time),
+    )?;
+    Ok((psbt, ordinal_and_output))
+}
+
+/// Wrap the selected signed seller inputs with our dummy utxos: the seller input `i` and its
+/// output stay at the same index for SINGLE|ANYONECANPAY, postage outputs receive the assets
+/// and new dummy utxos are split for the next fill.
+/// `replace_fee` is the fee of the tx being replaced, the new fee must exceed it.
+pub(crate) fn build_fill_psbt(
+    settings: &Settings,
+    ordinal_and_output: &[(usize, Ordinal, (TxIn, Prevout), TxOut)],
+    selected: &[bool],
+    mut is_rune: bool,
+    cardinal_utxos: Vec<Utxo>,
+    mut dummy_utxos: Vec<Utxo>,
+    pay_addr: Address,
+    change_addr: Address,
+    rev_addr: Address,
+    fee_rate: FeeRate,
+    replace_fee: Option<Amount>,
+    (version, lock_time): (Version, LockTime),
+) -> anyhow::Result<Psbt> {
+    let mut dummy_signed_tx_1 = DummyTransaction::new();
+
+    let mut inputs = Vec::new();
+    let mut signed_psbt_inputs = Vec::new();
+    let mut inputs_amount = Amount::ZERO;
+
+    let mut outputs = Vec::new();
+    let mut outputs_amount = Amount::ZERO;
+
     let mut edicts: Vec<Edict> = Vec::new();
     let mut edict_index = 0;
 
@@ -559,8 +692,8 @@ fn build_uncompleted_psbt(
     // }
 
     let mut buyer_unsigned_tx = Transaction {
-        version: snipe_tx.version,
-        lock_time: snipe_tx.lock_time,
+        version,
+        lock_time,
         input: inputs,
         output: outputs,
     };
@@ -570,7 +703,7 @@ fn build_uncompleted_psbt(
     let dummy_utxo = dummy_utxos.pop().ok_or(anyhow!("No dummy utxo"))?;
     let (mut inputs, mut psbt_inputs) = {
         if !is_rune {
-            dummy_signed_tx_1.append_input(pay_addr.clone(), None, None);
+            dummy_signed_tx_1.append_input(dummy_utxo.addr_or(&pay_addr), None, None);
             inputs_amount += dummy_utxo.value;
 
             (
@@ -590,7 +723,7 @@ fn build_uncompleted_psbt(
                         witness_utxo: Some({
                             TxOut {
                                 value: dummy_utxo.value,
-                                script_pubkey: pay_addr.script_pubkey(),
+                                script_pubkey: dummy_utxo.addr_or(&pay_addr).script_pubkey(),
                             }
                         }),
                         ..Default::default()
@@ -638,14 +771,14 @@ fn build_uncompleted_psbt(
             }
         });
 
-        dummy_signed_tx_1.append_input(pay_addr.clone(), None, None);
+        dummy_signed_tx_1.append_input(dummy_utxo.addr_or(&pay_addr), None, None);
 
         psbt_inputs.push({
             Input {
                 witness_utxo: Some({
                     TxOut {
                         value: dummy_utxo.value,
-                        script_pubkey: pay_addr.script_pubkey(),
+                        script_pubkey: dummy_utxo.addr_or(&pay_addr).script_pubkey(),
                     }
                 }),
                 // non_witness_utxo: non_witness_utxo,
@@ -722,11 +855,11 @@ fn build_uncompleted_psbt(
     outputs.push({
         TxOut {
             value: Amount::ZERO,
-            script_pubkey: pay_addr.script_pubkey(),
+            script_pubkey: change_addr.script_pubkey(),
         }
     });
 
-    dummy_signed_tx_1.append_output(pay_addr.script_pubkey());
+    dummy_signed_tx_1.append_output(change_addr.script_pubkey());
 
     let mut unsigned_tx = Transaction {
         version: buyer_unsigned_tx.version,
@@ -736,6 +869,8 @@ fn build_uncompleted_psbt(
     };
 
     let need_amount = outputs_amount - inputs_amount; // 需要的
+    let target = SelectionTarget::new(need_amount, fee_rate, &dummy_signed_tx_1, &change_addr);
+    let cardinal_utxos = settings.coin_selection.select(cardinal_utxos, &target);
     let mut extra_network_fee = Amount::ZERO; // RBF需要总交易费用大于原始交易
     let mut amount = Amount::ZERO; // 计算
     let mut ok = false;
@@ -748,7 +883,7 @@ fn build_uncompleted_psbt(
                 witness_utxo: Some({
                     TxOut {
                         value: utxo.value,
-                        script_pubkey: pay_addr.script_pubkey(),
+                        script_pubkey: utxo.addr_or(&pay_addr).script_pubkey(),
                     }
                 }),
                 ..Default::default()
@@ -766,7 +901,7 @@ fn build_uncompleted_psbt(
             }
         });
 
-        dummy_signed_tx_1.append_input(pay_addr.clone(), None, None);
+        dummy_signed_tx_1.append_input(utxo.addr_or(&pay_addr), None, None);
 
         // let a = dummy_signed_tx.base_size();
         // let b = dummy_signed_tx.vsize();
@@ -777,12 +912,19 @@ fn build_uncompleted_psbt(
         log::info!("[estimate] Size: {} vb", dummy_signed_tx_1.vsize());
 
         loop {
-            if let Some(unfilled) =
-                amount.checked_sub(network_fee + need_amount + extra_network_fee)
-            {
-                if (network_fee + extra_network_fee) > Amount::from_sat(snipe_pool_tx.fee + 1000) {
+            if let Some(change) = Change::settle(
+                amount,
+                need_amount,
+                network_fee + extra_network_fee,
+                target.change_fee(),
+                target.dust,
+            ) {
+                if replace_fee.map_or(true, |fee| {
+                    (network_fee + extra_network_fee) > fee + Amount::from_sat(1000)
+                }) {
                     // 大于原始交易的总费用才能上链
-                    unsigned_tx.output.last_mut().unwrap().value = unfilled; // 找零
+                    let index = unsigned_tx.output.len() - 1;
+                    change.apply(&mut unsigned_tx.output, index); // 找零
                     ok = true;
                     break 'outer;
                 }
@@ -801,11 +943,6 @@ fn build_uncompleted_psbt(
         bail!("No utxo or utxo not enough")
     }
 
-    // 找零小于粉尘值
-    if unsigned_tx.output.last().unwrap().value < pay_addr.script_pubkey().dust_value() {
-        unsigned_tx.output.pop();
-    }
-
     let o_len = unsigned_tx.output.len();
     let psbt = Psbt {
         unsigned_tx,
@@ -853,7 +990,61 @@ fn build_uncompleted_psbt(
         0f64
     };
     log::info!("[cost] Total: {} ,Average: {}", cost, arg);
-    Ok((psbt, ordinal_and_output))
+    Ok(psbt)
+}
+
+/// Inputs signed with SINGLE|ANYONECANPAY that carry an inscription or rune, with their
+/// committed outputs. Other inputs can not be reused in our tx.
+pub(crate) fn find_liftable_ordinals(
+    ordi_api: &ordinal::Client,
+    snipe_pool_tx: &esplora::Transaction,
+    snipe_tx: &Transaction,
+) -> anyhow::Result<Vec<(usize, Ordinal, (TxIn, Prevout), TxOut)>> {
+    let analysis = analyze_tx(snipe_tx, &esplora_prevouts(snipe_pool_tx)?);
+    let mut liftable = Vec::new();
+    for e in analysis {
+        let sighash = e.sighash.map_or("unknown".to_string(), |e| e.to_string());
+        if e.liftable && e.outputs == [e.index] {
+            log::info!("[sighash] Input: {} , Sighash: {}", e.index, sighash);
+            liftable.push(e.index);
+        } else {
+            log::debug!(
+                "[sighash] Input: {} , Sighash: {} , Outputs: {:?} , skipped",
+                e.index,
+                sighash,
+                e.outputs
+            );
+        }
+    }
+    if liftable.is_empty() {
+        bail!("No input signed with SINGLE|ANYONECANPAY");
+    }
+
+    log::info!("[waiting] Founding inscription and rune from origin tx");
+    let ordinals = ordi_api.fetch_outputs(
+        liftable
+            .iter()
+            .map(|i| &snipe_tx.input[*i].previous_output)
+            .collect(),
+    )?;
+    if ordinals.is_empty() {
+        bail!("Not found inscription or rune");
+    }
+    Ok(ordinals
+        .into_iter()
+        .map(|(j, ordi)| {
+            let i = liftable[j];
+            (
+                i,
+                ordi,
+                (
+                    snipe_tx.input[i].clone(),
+                    snipe_pool_tx.vin[i].prevout.clone(),
+                ),
+                snipe_tx.output[i].clone(),
+            )
+        })
+        .collect())
 }
 
 fn get_poison(
@@ -867,12 +1058,6 @@ fn get_poison(
     log::info!("[PoisonWallet] Pay: {} ", wallet.pay_addr());
     log::info!("[PoisonWallet] Ordi: {} ", wallet.ordi_addr());
 
-    let mut tx = Transaction {
-        version: Version::TWO,
-        lock_time: LockTime::ZERO,
-        input: vec![],
-        output: vec![],
-    };
     // 挑选一组符文
     let utxos = get_value_utxos(btc_api, &wallet.ordi_addr().to_string(), POSTAGE)?;
     let ordi_utxos = utxos
@@ -890,43 +1075,16 @@ fn get_poison(
 
     let outpoint = ordi.outpoint().ok_or(anyhow!("No posion ordinal"))?;
     log::info!("[poison] {} ", ordi.display());
-    tx.input.push(TxIn {
-        previous_output: outpoint.clone(),
-        sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
-        ..default()
-    });
-    tx.output.push(TxOut {
-        value: pay_amount,
-        script_pubkey: wallet.pay_addr().script_pubkey(),
-    });
-
-    let mut psbt = Psbt {
-        unsigned_tx: tx,
-        version: 0,
-        xpub: Default::default(),
-        proprietary: Default::default(),
-        unknown: Default::default(),
-        inputs: vec![],
-        outputs: vec![default(); 1],
-    };
-
-    psbt.inputs.push(Input {
-        witness_utxo: Some({
-            TxOut {
-                value: POSTAGE,
-                script_pubkey: wallet.ordi_addr().script_pubkey(),
-            }
-        }),
-        sighash_type: Some(PsbtSighashType::from(
-            TapSighashType::SinglePlusAnyoneCanPay,
-        )),
-        ..default()
-    });
-
-    let ok = wallet.sign_swap(&mut psbt)?;
-    if !ok {
-        bail!("Sign failed")
-    }
+    let mut psbt = build_listing_psbt(
+        &wallet,
+        outpoint,
+        TxOut {
+            value: POSTAGE,
+            script_pubkey: wallet.ordi_addr().script_pubkey(),
+        },
+        pay_amount,
+        wallet.pay_addr().script_pubkey(),
+    )?;
 
     psbt.serialize_hex().print();
 
@@ -986,50 +1144,8 @@ fn build_uncompleted_psbt_without_dummy(
     let mut outputs_amount = Amount::ZERO;
 
     let mut is_rune = typ == Type::Rune;
-    let snipe_utxos = snipe_tx
-        .input
-        .iter()
-        .map(|e| &e.previous_output)
-        .collect::<Vec<_>>();
-
-    log::info!("[waiting] Founding inscription and rune from origin tx");
-    let ordinals = ordi_api.fetch_outputs(snipe_utxos)?;
-
-    let mut ordinal_and_output = ordinals
-        .into_iter()
-        .map(|(i, ordi)| {
-            (
-                i,
-                ordi,
-                (
-                    snipe_tx.input[i].clone(),
-                    snipe_pool_tx.vin[i].prevout.clone(),
-                ),
-                snipe_tx.output[i].clone(),
-            )
-        })
-        .collect::<Vec<_>>();
-
-    if ordinal_and_output.len() < 1 {
-        bail!("Not found inscription or rune");
-    }
-
-    let mut selected = if ordinal_and_output.len() > 1 {
-        let ordinal_options = ordinal_and_output
-            .iter()
-            .map(|(_, ordinal, _, txout)| ordinal.display_value(txout.value))
-            .collect::<Vec<_>>();
-        let selected = console_utils::input::multiselect(
-            "Multiple runes or inscriptions were found. Please select one or more (By SPACE Key)",
-            &ordinal_options
-                .iter()
-                .map(|e| e.as_str())
-                .collect::<Vec<_>>(),
-        );
-        selected
-    } else {
-        vec![true; ordinal_and_output.len()]
-    };
+    let ordinal_and_output = find_liftable_ordinals(ordi_api, snipe_pool_tx, snipe_tx)?;
+    let selected = vec![true; ordinal_and_output.len()];
 
     for (index, (_, ordinal, (txin, prevout), txout)) in ordinal_and_output.iter().enumerate() {
         if !selected[index] {
@@ -1128,6 +1244,8 @@ fn build_uncompleted_psbt_without_dummy(
     // merge output
     let mut psbt_inputs = signed_psbt_inputs;
     let need_amount = outputs_amount - inputs_amount; // 需要的
+    let target = SelectionTarget::new(need_amount, fee_rate, &dummy_signed_tx_1, &pay_addr);
+    let cardinal_utxos = settings.coin_selection.select(cardinal_utxos, &target);
     let mut extra_network_fee = Amount::ZERO; // RBF需要总交易费用大于原始交易
     let mut amount = Amount::ZERO; // 计算
     let mut ok = false;
@@ -1142,7 +1260,7 @@ fn build_uncompleted_psbt_without_dummy(
                     witness_utxo: Some({
                         TxOut {
                             value: utxo.value,
-                            script_pubkey: pay_addr.script_pubkey(),
+                            script_pubkey: utxo.addr_or(&pay_addr).script_pubkey(),
                         }
                     }),
                     ..Default::default()
@@ -1166,7 +1284,7 @@ fn build_uncompleted_psbt_without_dummy(
                     witness_utxo: Some({
                         TxOut {
                             value: utxo.value,
-                            script_pubkey: pay_addr.script_pubkey(),
+                            script_pubkey: utxo.addr_or(&pay_addr).script_pubkey(),
                         }
                     }),
                     ..Default::default()
@@ -1184,21 +1302,21 @@ fn build_uncompleted_psbt_without_dummy(
                 }
             });
         }
-        dummy_signed_tx_1.append_input(pay_addr.clone(), None, None);
+        dummy_signed_tx_1.append_input(utxo.addr_or(&pay_addr), None, None);
 
         let network_fee = fee_rate.fee_vb(dummy_signed_tx_1.vsize() as u64).unwrap();
 
         log::info!("[estimate] Size: {} vb", dummy_signed_tx_1.vsize());
 
         loop {
-            if let Some(unfilled) =
-                amount.checked_sub(network_fee + need_amount + extra_network_fee)
-            {
-                // 找零小于粉尘值
-                if unfilled < pay_addr.script_pubkey().dust_value() {
-                    break;
-                }
-
+            // the first output receives the assets, it can not be dropped as changeless
+            if let Some(Change::Output(unfilled)) = Change::settle(
+                amount,
+                need_amount,
+                network_fee + extra_network_fee,
+                target.change_fee(),
+                target.dust,
+            ) {
                 if (network_fee + extra_network_fee) > Amount::from_sat(snipe_pool_tx.fee + 1000) {
                     // 大于原始交易的总费用才能上链
                     unsigned_tx.output.first_mut().unwrap().value = unfilled; // 找零
@@ -1640,7 +1758,6 @@ fn build_uncompleted_psbt_without_dummy(
 mod tests {
     use std::str::FromStr;
 
-    use bdk::SignOptions;
     use bip39::Mnemonic;
     use bitcoin::{
         bip32::{DerivationPath, Xpriv},
@@ -1650,10 +1767,7 @@ mod tests {
     };
 
     use super::*;
-    use crate::{
-        default,
-        wallet::{MnemonicWallet, Mode},
-    };
+    use crate::wallet::{MnemonicWallet, Mode};
 
     #[test]
     fn test_() {
@@ -1689,20 +1803,11 @@ mod tests {
     fn test_vi() {
         let mut psbt = Psbt::deserialize(&Vec::from_hex("70736274ff0100f302000000024a00299e938d626b0e28025f17d9475dc36535b0222f81765f7acd27b485dcbb0100000000ffffffff1183d5d4ec262e5fccb8c3f146a69235a3a0194a86f4355251ccb6d022b929440900000000ffffffff04093d0000000000002251205eb470e0dfce1da8d410773f6cc40489b48ec78e5b2eaf299be12bb64af3679722020000000000002251201a81e8dd9eb89088bc851041ae6aaa75c3c4e4bc43454821e26003e68af9495d00000000000000000d6a5d0a00c0a233970392f40101e10e0100000000002251201a81e8dd9eb89088bc851041ae6aaa75c3c4e4bc43454821e26003e68af9495d000000000001012b5e010000000000002251205eb470e0dfce1da8d410773f6cc40489b48ec78e5b2eaf299be12bb64af36797011720613280f8c7fff7b128b81c15845bae82ad612e6b4b5210b024fe7d4c624eaf4a0001012b22610100000000002251201a81e8dd9eb89088bc851041ae6aaa75c3c4e4bc43454821e26003e68af9495d0117209bd68b2123e2d08e923f637d80a66240be5e1bd0b876a01b874f3f7a4a1650dd0000000000").unwrap()).unwrap();
 
-        let wallet = MnemonicWallet::new("", Mode::Other, Network::Bitcoin).unwrap();
+        let wallet = MnemonicWallet::new("", Mode::Unisat, 0, Network::Bitcoin).unwrap();
 
         let ok = wallet.sign(&mut psbt).unwrap();
         println!("{}", ok);
-        let f = wallet
-            .pay_wallet
-            .finalize_psbt(
-                &mut psbt,
-                SignOptions {
-                    trust_witness_utxo: true,
-                    ..default()
-                },
-            )
-            .unwrap();
+        let f = wallet.finalize(&mut psbt).unwrap();
 
         let signed_tx = psbt.clone().extract_tx().unwrap();
         let hex = encode::serialize_hex(&signed_tx);
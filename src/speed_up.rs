@@ -1,63 +1,519 @@
-use anyhow::bail;
-use bitcoin::{consensus::encode, FeeRate, Weight};
-use console_utils::input::select;
+use anyhow::{anyhow, bail};
+use bitcoin::{
+    absolute::LockTime, consensus::encode, psbt::Input, transaction::Version, Address, Amount,
+    FeeRate, Network, OutPoint, Psbt, ScriptBuf, Sequence, Transaction, TxIn, TxOut,
+};
+use ordinals::Runestone;
 
-use crate::{send, setting::Settings, snipe::get_utxos, utils::select_confirm};
+use crate::{
+    btc_api::{esplora, esplora::Utxo},
+    coin_select::{Change, CoinSelector, SelectionTarget},
+    default,
+    dummy_transaction::DummyTransaction,
+    envelope::has_envelope,
+    package::{ancestor_package, Package},
+    setting::Settings,
+    snipe::get_wallet_utxos,
+    utils::{print_table, select_confirm},
+    wallet::Wallet,
+};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Strategy {
+    /// Replace the tx, all inputs are ours
+    RBF,
+    /// Spend our outputs of the tx with a high fee child
+    CPFP,
+}
 
 pub fn speed_up(
     settings: Settings,
+    wallet: &dyn Wallet,
     txid: &str,
-    increase_rate: u64,
+    fee_rate: u64,
     broadcast: bool,
 ) -> anyhow::Result<()> {
     let btc_api = settings.btc_api();
-    let wallet = settings.wallet()?;
-
     let pay_addr = wallet.pay_addr();
-    let tx = btc_api.get_transaction(txid)?;
-    let fee_rate =
-        FeeRate::from_sat_per_vb(tx.fee / Weight::from_wu(tx.weight).to_vbytes_ceil()).unwrap();
+    let fee_rate = FeeRate::from_sat_per_vb(fee_rate).ok_or(anyhow!("fee_rate is invalid"))?;
 
-    let options = tx
-        .vout
-        .iter()
-        .enumerate()
-        .filter(|(i, e)| e.scriptpubkey_address == pay_addr.to_string())
-        .map(|(i, e)| (i, e, format!("{} {}", e.scriptpubkey_address, e.value)))
-        .collect::<Vec<_>>();
+    let pool_tx = btc_api.get_transaction(txid)?;
+    if pool_tx.status.confirmed {
+        bail!("Tx confirmed, no need to speed up");
+    }
+    let tx = btc_api.get_btc_transaction(txid)?;
 
-    let selected = select(
-        "Please select utxo",
-        &options
-            .iter()
-            .map(|((_, _, display))| display.as_str())
-            .collect::<Vec<_>>(),
+    let origin_vsize = tx.vsize() as u64;
+    let origin_fee = Amount::from_sat(pool_tx.fee);
+    log::info!(
+        "[origin tx] TotalFee: {} sat, FeeRate: {:.1} sat/vb, Size: {} vb ",
+        origin_fee.to_sat(),
+        origin_fee.to_sat() as f64 / origin_vsize as f64,
+        origin_vsize,
     );
+    if origin_fee >= fee_rate.fee_vb(origin_vsize).unwrap() {
+        bail!(
+            "Tx fee rate already reached {} sat/vb",
+            fee_rate.to_sat_per_vb_ceil()
+        );
+    }
 
-    let selected = options[selected].0;
+    let pay_addrs = wallet
+        .pay_addrs()
+        .iter()
+        .map(|e| e.to_string())
+        .collect::<Vec<_>>();
+    let all_inputs_owned = pool_tx
+        .vin
+        .iter()
+        .all(|e| pay_addrs.contains(&e.prevout.scriptpubkey_address));
+    let strategy = if all_inputs_owned && tx.is_explicitly_rbf() {
+        Strategy::RBF
+    } else {
+        Strategy::CPFP
+    };
+    log::info!("[speed up] Strategy: {:?}", strategy);
 
-    let (utxos, _) = get_utxos(&btc_api, &pay_addr.to_string())?;
-    let mut psbt = send::build_psbt(
-        pay_addr.clone(),
-        vec![(pay_addr.clone(), tx.vout[selected].value)],
-        fee_rate,
-        utxos,
-    )?;
+    let (utxos, _) = get_wallet_utxos(&settings, wallet)?;
+    let mut package = Package::default();
+    let mut psbt = match strategy {
+        Strategy::RBF => build_rbf_psbt(
+            &pool_tx,
+            &tx,
+            pay_addr.clone(),
+            &wallet.change_addrs(),
+            wallet.change_addr(),
+            settings.network,
+            fee_rate,
+            utxos,
+            &settings.coin_selection,
+        )?,
+        Strategy::CPFP => {
+            package = ancestor_package(&btc_api, &[tx.txid()])?;
+            build_cpfp_psbt(
+                cpfp_outputs(&settings, wallet, &tx)?,
+                &package,
+                pay_addr.clone(),
+                fee_rate,
+                utxos,
+                &settings.coin_selection,
+            )?
+        }
+    };
 
     let ok = wallet.sign(&mut psbt)?;
     if !ok {
         bail!("Sign failed")
     }
 
-    let signed_tx = psbt.extract_tx()?;
-
+    let fee = psbt.fee()?;
+    let signed_tx = psbt.clone().extract_tx()?;
     let hex = encode::serialize_hex(&signed_tx);
 
+    log::info!(
+        "[{}] FeeRate: {:.1} sat/vb , TotalFee: {} sat , Size: {} vb",
+        signed_tx.txid(),
+        fee.to_sat() as f64 / signed_tx.vsize() as f64,
+        fee.to_sat(),
+        signed_tx.vsize()
+    );
+    if strategy == Strategy::CPFP {
+        let package_fee = package.fee() + fee;
+        let package_vsize = package.vsize + signed_tx.vsize() as u64;
+        log::info!(
+            "[package] FeeRate: {:.1} sat/vb , TotalFee: {} sat , Size: {} vb",
+            package_fee.to_sat() as f64 / package_vsize as f64,
+            package_fee.to_sat(),
+            package_vsize
+        );
+    }
+    print_table(&psbt, settings.network);
+
     if broadcast {
-        if select_confirm("") {
+        if select_confirm("Please confirm") {
             settings.broadcast(&hex)?;
         }
     }
 
     Ok(())
 }
+
+/// Rebuild the tx with the same inputs and outputs, the fee is taken from the change output
+/// and topped up by other utxos if the change is not enough. Only an output paying one of
+/// `change_addrs` is change, never one paying `pay_addr` that may be a recipient or a dummy.
+fn build_rbf_psbt(
+    pool_tx: &esplora::Transaction,
+    tx: &Transaction,
+    pay_addr: Address,
+    change_addrs: &[Address],
+    change_addr: Address,
+    network: Network,
+    fee_rate: FeeRate,
+    utxos: Vec<Utxo>,
+    coin_selector: &dyn CoinSelector,
+) -> anyhow::Result<Psbt> {
+    let mut unsigned_tx = Transaction {
+        version: tx.version,
+        lock_time: tx.lock_time,
+        input: vec![],
+        output: tx.output.clone(),
+    };
+    let mut dummy_tx = DummyTransaction::new();
+    let mut psbt_inputs = Vec::new();
+    let mut amount = Amount::ZERO;
+
+    for (txin, vin) in tx.input.iter().zip(pool_tx.vin.iter()) {
+        unsigned_tx.input.push(TxIn {
+            previous_output: txin.previous_output,
+            script_sig: Default::default(),
+            sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+            witness: Default::default(),
+        });
+        let script_pubkey = ScriptBuf::from_hex(&vin.prevout.scriptpubkey)?;
+        dummy_tx.append_input(Address::from_script(&script_pubkey, network)?, None, None);
+        psbt_inputs.push(Input {
+            witness_utxo: Some(TxOut {
+                value: vin.prevout.value,
+                script_pubkey,
+            }),
+            ..default()
+        });
+        amount += vin.prevout.value;
+    }
+
+    // change, the last output to an internal address
+    let change_index = match unsigned_tx.output.iter().rposition(|e| {
+        change_addrs
+            .iter()
+            .any(|addr| addr.script_pubkey() == e.script_pubkey)
+    }) {
+        Some(index) => index,
+        None => {
+            unsigned_tx.output.push(TxOut {
+                value: Amount::ZERO,
+                script_pubkey: change_addr.script_pubkey(),
+            });
+            unsigned_tx.output.len() - 1
+        }
+    };
+    for output in unsigned_tx.output.iter() {
+        dummy_tx.append_output(output.script_pubkey.clone());
+    }
+    let need_amount = unsigned_tx
+        .output
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| *i != change_index)
+        .map(|(_, e)| e.value)
+        .sum::<Amount>();
+
+    let vsize = dummy_tx.vsize() as u64;
+    // what BIP125 asks on top of the rate
+    let replaced_fee = (Amount::from_sat(pool_tx.fee)
+        + FeeRate::BROADCAST_MIN.fee_vb(vsize).unwrap())
+    .checked_sub(fee_rate.fee_vb(vsize).unwrap())
+    .unwrap_or(Amount::ZERO);
+    let target = SelectionTarget::new(
+        (need_amount + replaced_fee)
+            .checked_sub(amount)
+            .unwrap_or(Amount::ZERO),
+        fee_rate,
+        &dummy_tx,
+        &change_addr,
+    );
+    let mut utxos = coin_selector.select(utxos, &target).into_iter();
+    loop {
+        let vsize = dummy_tx.vsize() as u64;
+        // BIP125: pay for the replaced tx and the new tx's own bandwidth
+        let network_fee = fee_rate
+            .fee_vb(vsize)
+            .unwrap()
+            .max(Amount::from_sat(pool_tx.fee) + FeeRate::BROADCAST_MIN.fee_vb(vsize).unwrap());
+
+        match Change::settle(
+            amount,
+            need_amount,
+            network_fee,
+            target.change_fee(),
+            target.dust,
+        ) {
+            // a tx needs at least one output
+            Some(Change::Dropped) if unsigned_tx.output.len() == 1 => {}
+            Some(change) => {
+                change.apply(&mut unsigned_tx.output, change_index);
+                break;
+            }
+            None => {}
+        }
+
+        let Some(utxo) = utxos.next() else {
+            bail!("No utxo or not enough")
+        };
+        unsigned_tx.input.push(TxIn {
+            previous_output: OutPoint {
+                txid: utxo.txid,
+                vout: utxo.vout,
+            },
+            script_sig: Default::default(),
+            sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+            witness: Default::default(),
+        });
+        dummy_tx.append_input(utxo.addr_or(&pay_addr), None, None);
+        psbt_inputs.push(Input {
+            witness_utxo: Some(TxOut {
+                value: utxo.value,
+                script_pubkey: utxo.addr_or(&pay_addr).script_pubkey(),
+            }),
+            ..default()
+        });
+        amount += utxo.value;
+    }
+
+    let o_len = unsigned_tx.output.len();
+    let psbt = Psbt {
+        unsigned_tx,
+        version: 0,
+        xpub: Default::default(),
+        proprietary: Default::default(),
+        unknown: Default::default(),
+        inputs: psbt_inputs,
+        outputs: vec![default(); o_len],
+    };
+    Ok(psbt)
+}
+
+/// The indexer never sees mempool outputs, an unconfirmed tx only makes cardinal outputs if
+/// all its inputs are cardinal and it reveals no inscription nor carries a runestone.
+fn is_cardinal_tx(
+    tx: &Transaction,
+    is_cardinal: impl Fn(&OutPoint) -> anyhow::Result<bool>,
+) -> anyhow::Result<bool> {
+    if has_envelope(tx) {
+        log::info!("[cpfp] {} reveals an inscription", tx.txid());
+        return Ok(false);
+    }
+    if Runestone::decipher(tx).is_some() {
+        log::info!("[cpfp] {} carries a runestone", tx.txid());
+        return Ok(false);
+    }
+    for input in &tx.input {
+        if !is_cardinal(&input.previous_output)? {
+            log::info!(
+                "[cpfp] {} spends {}, inscription or rune or not indexed",
+                tx.txid(),
+                input.previous_output
+            );
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Outputs of the parent paying a pay or change address of the wallet that carry no
+/// inscription or rune, see `is_cardinal_tx`. Otherwise they are only spent if allowed by
+/// `spendable_outpoints`.
+fn cpfp_outputs(
+    settings: &Settings,
+    wallet: &dyn Wallet,
+    parent: &Transaction,
+) -> anyhow::Result<Vec<(Address, OutPoint, TxOut)>> {
+    let ordi_api = settings.ordi_api();
+    let cardinal = is_cardinal_tx(parent, |e| ordi_api.is_cardinal(e))?;
+    let mut addrs = wallet.pay_addrs();
+    addrs.push(wallet.change_addr());
+    let parent_txid = parent.txid();
+    let mut outputs = Vec::new();
+    let mut owned = false;
+    for (vout, output) in parent.output.iter().enumerate() {
+        let Some(addr) = addrs
+            .iter()
+            .find(|e| e.script_pubkey() == output.script_pubkey)
+        else {
+            continue;
+        };
+        owned = true;
+        let out_point = OutPoint {
+            txid: parent_txid,
+            vout: vout as u32,
+        };
+        if cardinal || settings.spendable_outpoints.contains(&out_point) {
+            outputs.push((addr.clone(), out_point, output.clone()));
+        }
+    }
+    if !owned {
+        bail!("No output of the tx pays to the wallet, can not CPFP");
+    }
+    if outputs.is_empty() {
+        bail!(
+            "No cardinal output of the tx, can not CPFP. Allow them by `spendable_outpoints` if \
+             they carry no inscription or rune"
+        );
+    }
+    Ok(outputs)
+}
+
+/// Spend `outputs` of the parent, the child fee lifts the package (unconfirmed ancestors +
+/// child) to `fee_rate`.
+fn build_cpfp_psbt(
+    outputs: Vec<(Address, OutPoint, TxOut)>,
+    package: &Package,
+    pay_addr: Address,
+    fee_rate: FeeRate,
+    utxos: Vec<Utxo>,
+    coin_selector: &dyn CoinSelector,
+) -> anyhow::Result<Psbt> {
+    let mut unsigned_tx = Transaction {
+        version: Version::TWO,
+        lock_time: LockTime::ZERO,
+        input: vec![],
+        output: vec![],
+    };
+    let mut dummy_tx = DummyTransaction::new();
+    let mut psbt_inputs = Vec::new();
+    let mut amount = Amount::ZERO;
+
+    for (addr, out_point, output) in outputs {
+        unsigned_tx.input.push(TxIn {
+            previous_output: out_point,
+            script_sig: Default::default(),
+            sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+            witness: Default::default(),
+        });
+        dummy_tx.append_input(addr, None, None);
+        amount += output.value;
+        psbt_inputs.push(Input {
+            witness_utxo: Some(output),
+            ..default()
+        });
+    }
+
+    // change
+    unsigned_tx.output.push(TxOut {
+        value: Amount::ZERO,
+        script_pubkey: pay_addr.script_pubkey(),
+    });
+    dummy_tx.append_output(pay_addr.script_pubkey());
+
+    let vsize = dummy_tx.vsize() as u64;
+    // what the ancestors ask on top of the rate
+    let package_fee = package
+        .child_fee(vsize, fee_rate)
+        .checked_sub(fee_rate.fee_vb(vsize).unwrap())
+        .unwrap_or(Amount::ZERO);
+    let target = SelectionTarget::new(
+        package_fee.checked_sub(amount).unwrap_or(Amount::ZERO),
+        fee_rate,
+        &dummy_tx,
+        &pay_addr,
+    );
+    let mut utxos = coin_selector.select(utxos, &target).into_iter();
+    loop {
+        let child_vsize = dummy_tx.vsize() as u64;
+        let network_fee = package.child_fee(child_vsize, fee_rate);
+
+        // the change is the only output of the child, it can not be dropped
+        if let Some(Change::Output(unfilled)) = Change::settle(
+            amount,
+            Amount::ZERO,
+            network_fee,
+            target.change_fee(),
+            target.dust,
+        ) {
+            unsigned_tx.output.last_mut().unwrap().value = unfilled;
+            break;
+        }
+
+        let Some(utxo) = utxos.next() else {
+            bail!("No utxo or not enough")
+        };
+        unsigned_tx.input.push(TxIn {
+            previous_output: OutPoint {
+                txid: utxo.txid,
+                vout: utxo.vout,
+            },
+            script_sig: Default::default(),
+            sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+            witness: Default::default(),
+        });
+        dummy_tx.append_input(utxo.addr_or(&pay_addr), None, None);
+        psbt_inputs.push(Input {
+            witness_utxo: Some(TxOut {
+                value: utxo.value,
+                script_pubkey: utxo.addr_or(&pay_addr).script_pubkey(),
+            }),
+            ..default()
+        });
+        amount += utxo.value;
+    }
+
+    let o_len = unsigned_tx.output.len();
+    let psbt = Psbt {
+        unsigned_tx,
+        version: 0,
+        xpub: Default::default(),
+        proprietary: Default::default(),
+        unknown: Default::default(),
+        inputs: psbt_inputs,
+        outputs: vec![default(); o_len],
+    };
+    Ok(psbt)
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::{
+        hashes::Hash,
+        secp256k1::{All, Secp256k1},
+        Txid,
+    };
+
+    use super::*;
+    use crate::envelope::Envelope;
+
+    fn parent(secp: &Secp256k1<All>) -> Transaction {
+        let envelope = Envelope::new(secp, &[], None).unwrap();
+        Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(Txid::all_zeros(), 0),
+                script_sig: Default::default(),
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                witness: Default::default(),
+            }],
+            output: vec![TxOut {
+                value: Amount::from_sat(10_000),
+                script_pubkey: envelope.address(bitcoin::Network::Bitcoin).script_pubkey(),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_is_cardinal_tx() {
+        let secp = Secp256k1::new();
+        let parent = parent(&secp);
+        let parent_txid = parent.txid();
+        // only the confirmed input is indexed, never the output of the parent
+        let indexed = |e: &OutPoint| {
+            if e.txid == parent_txid {
+                bail!("{} not indexed", e)
+            }
+            Ok(true)
+        };
+        assert!(is_cardinal_tx(&parent, indexed).unwrap());
+        assert!(!is_cardinal_tx(&parent, |_| Ok(false)).unwrap());
+
+        let mut with_runestone = parent.clone();
+        with_runestone.output.push(TxOut {
+            value: Amount::ZERO,
+            script_pubkey: ScriptBuf::from_bytes(Runestone::default().encipher().into_bytes()),
+        });
+        assert!(!is_cardinal_tx(&with_runestone, indexed).unwrap());
+
+        let mut with_envelope = parent.clone();
+        with_envelope.input[0].witness = Envelope::new(&secp, &[], Some(b"a"))
+            .unwrap()
+            .dummy_witness();
+        assert!(!is_cardinal_tx(&with_envelope, indexed).unwrap());
+    }
+}
@@ -0,0 +1,207 @@
+use std::time::{Duration, Instant};
+
+pub mod poll;
+pub mod ws;
+#[cfg(feature = "zmq")]
+pub mod zmq;
+
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TxEvent {
+    Confirmed {
+        txid: String,
+        block_height: Option<u64>,
+    },
+    Replaced {
+        txid: String,
+        by: String,
+    },
+    /// A tx touching a tracked address entered the mempool
+    AddressTx {
+        address: String,
+        txid: String,
+    },
+    /// Any tx entered the mempool, see `track_mempool`
+    MempoolTx {
+        txid: String,
+    },
+}
+
+/// Push based tx tracking
+pub trait TxTracker {
+    fn track_tx(&mut self, txid: &str) -> anyhow::Result<()>;
+
+    /// Adds `address` to the tracked ones
+    fn track_address(&mut self, address: &str) -> anyhow::Result<()>;
+
+    /// Every tx entering the mempool
+    fn track_mempool(&mut self) -> anyhow::Result<()>;
+
+    /// None if nothing happened within `timeout`
+    fn next_event(&mut self, timeout: Duration) -> anyhow::Result<Option<TxEvent>>;
+}
+
+/// A push tracker reconnected with backoff when it drops, `fallback` polls meanwhile.
+/// Subscriptions are replayed on every new connection.
+pub struct Reconnect {
+    connect: Box<dyn Fn() -> anyhow::Result<Box<dyn TxTracker>>>,
+    tracker: Option<Box<dyn TxTracker>>,
+    fallback: poll::Client,
+    backoff: Duration,
+    retry_at: Instant,
+    txid: Option<String>,
+    addresses: Vec<String>,
+    mempool: bool,
+}
+
+impl Reconnect {
+    pub fn new(
+        connect: impl Fn() -> anyhow::Result<Box<dyn TxTracker>> + 'static,
+        fallback: poll::Client,
+    ) -> anyhow::Result<Self> {
+        let tracker = connect()?;
+        Ok(Self {
+            connect: Box::new(connect),
+            tracker: Some(tracker),
+            fallback,
+            backoff: MIN_BACKOFF,
+            retry_at: Instant::now(),
+            txid: None,
+            addresses: Vec::new(),
+            mempool: false,
+        })
+    }
+
+    fn subscribe(&self, tracker: &mut dyn TxTracker) -> anyhow::Result<()> {
+        if let Some(txid) = &self.txid {
+            tracker.track_tx(txid)?;
+        }
+        for address in &self.addresses {
+            tracker.track_address(address)?;
+        }
+        if self.mempool {
+            tracker.track_mempool()?;
+        }
+        Ok(())
+    }
+
+    fn disconnect(&mut self, e: anyhow::Error) {
+        log::warn!(
+            "[tracker] Disconnected: {}, polling until reconnected in {:?}",
+            e,
+            self.backoff
+        );
+        self.tracker = None;
+        self.retry_at = Instant::now() + self.backoff;
+    }
+
+    fn reconnect(&mut self) {
+        let result = (self.connect)().and_then(|mut tracker| {
+            self.subscribe(tracker.as_mut())?;
+            Ok(tracker)
+        });
+        match result {
+            Ok(tracker) => {
+                log::info!("[tracker] Reconnected");
+                self.tracker = Some(tracker);
+                self.backoff = MIN_BACKOFF;
+            }
+            Err(e) => {
+                self.backoff = (self.backoff * 2).min(MAX_BACKOFF);
+                self.disconnect(e);
+            }
+        }
+    }
+
+    /// Apply a subscription to the fallback and the connected tracker
+    fn track(
+        &mut self,
+        f: impl Fn(&mut dyn TxTracker) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        f(&mut self.fallback)?;
+        if let Some(tracker) = self.tracker.as_mut() {
+            if let Err(e) = f(tracker.as_mut()) {
+                self.disconnect(e);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl TxTracker for Reconnect {
+    fn track_tx(&mut self, txid: &str) -> anyhow::Result<()> {
+        self.txid = Some(txid.to_string());
+        self.track(|e| e.track_tx(txid))
+    }
+
+    fn track_address(&mut self, address: &str) -> anyhow::Result<()> {
+        if !self.addresses.iter().any(|e| e == address) {
+            self.addresses.push(address.to_string());
+        }
+        self.track(|e| e.track_address(address))
+    }
+
+    fn track_mempool(&mut self) -> anyhow::Result<()> {
+        self.mempool = true;
+        self.track(|e| e.track_mempool())
+    }
+
+    fn next_event(&mut self, timeout: Duration) -> anyhow::Result<Option<TxEvent>> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let now = Instant::now();
+            if self.tracker.is_none() && now >= self.retry_at {
+                self.reconnect();
+            }
+            let left = deadline.saturating_duration_since(now);
+            if left.is_zero() {
+                return Ok(None);
+            }
+            match self.tracker.as_mut() {
+                Some(tracker) => match tracker.next_event(left) {
+                    Ok(event) => return Ok(event),
+                    Err(e) => self.disconnect(e),
+                },
+                None => {
+                    let left = left.min(self.retry_at.saturating_duration_since(now));
+                    match self.fallback.next_event(left) {
+                        Ok(Some(event)) => return Ok(Some(event)),
+                        Ok(None) => {}
+                        Err(e) => log::warn!("[tracker] Poll failed: {}", e),
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// How a tracked tx left the mempool
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TxOutcome {
+    Confirmed,
+    Replaced { by: String },
+}
+
+/// Block until `txid` is confirmed or replaced
+pub(crate) fn wait_tx(
+    tracker: &mut dyn TxTracker,
+    txid: &str,
+    timeout: Duration,
+) -> anyhow::Result<TxOutcome> {
+    tracker.track_tx(txid)?;
+    loop {
+        match tracker.next_event(timeout)? {
+            Some(TxEvent::Confirmed { txid: e, .. }) if e == txid => {
+                return Ok(TxOutcome::Confirmed)
+            }
+            Some(TxEvent::Replaced { txid: e, by }) if e == txid => {
+                log::info!("[{}] Replaced by {}", txid, by);
+                return Ok(TxOutcome::Replaced { by });
+            }
+            Some(_) => {}
+            None => log::info!("[monitor] Running..."),
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    thread,
+    time::{Duration, Instant},
+};
+
+use super::{TxEvent, TxTracker};
+use crate::btc_api::esplora;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(3);
+
+/// Esplora polling, used while a push tracker is disconnected
+pub struct Client {
+    btc_api: esplora::Client,
+    txid: Option<String>,
+    /// Unconfirmed txs of each address at the last poll, None before the first one
+    addresses: Vec<(String, Option<HashSet<String>>)>,
+    mempool: Option<Option<HashSet<String>>>,
+    events: VecDeque<TxEvent>,
+    next_poll: Instant,
+}
+
+impl Client {
+    pub fn new(btc_api: esplora::Client) -> Self {
+        Self {
+            btc_api,
+            txid: None,
+            addresses: Vec::new(),
+            mempool: None,
+            events: VecDeque::new(),
+            next_poll: Instant::now(),
+        }
+    }
+
+    fn poll(&mut self) -> anyhow::Result<()> {
+        if let Some(txid) = self.txid.clone() {
+            let by = self
+                .btc_api
+                .get_rbf_tx(&txid)?
+                .replacements
+                .map(|e| e.tx.txid)
+                .filter(|e| *e != txid);
+            if let Some(by) = by {
+                self.events.push_back(TxEvent::Replaced { txid, by });
+                self.txid = None;
+            } else {
+                let status = self.btc_api.get_transaction(&txid)?.status;
+                if status.confirmed {
+                    self.events.push_back(TxEvent::Confirmed {
+                        txid,
+                        block_height: status.block_height,
+                    });
+                    self.txid = None;
+                }
+            }
+        }
+        for (address, last) in &mut self.addresses {
+            let txids = self
+                .btc_api
+                .get_transactions(address)?
+                .into_iter()
+                .filter(|e| !e.status.confirmed)
+                .map(|e| e.txid)
+                .collect::<HashSet<_>>();
+            for txid in new_txids(last, &txids) {
+                self.events.push_back(TxEvent::AddressTx {
+                    address: address.clone(),
+                    txid,
+                });
+            }
+            *last = Some(txids);
+        }
+        if let Some(last) = &mut self.mempool {
+            let txids = self
+                .btc_api
+                .get_mempool_recent()?
+                .into_iter()
+                .map(|e| e.txid)
+                .collect::<HashSet<_>>();
+            for txid in new_txids(last, &txids) {
+                self.events.push_back(TxEvent::MempoolTx { txid });
+            }
+            *last = Some(txids);
+        }
+        Ok(())
+    }
+}
+
+/// `txids` missing from `last`, nothing on the first poll
+fn new_txids(last: &Option<HashSet<String>>, txids: &HashSet<String>) -> Vec<String> {
+    match last {
+        Some(last) => txids.difference(last).cloned().collect(),
+        None => vec![],
+    }
+}
+
+impl TxTracker for Client {
+    fn track_tx(&mut self, txid: &str) -> anyhow::Result<()> {
+        self.txid = Some(txid.to_string());
+        Ok(())
+    }
+
+    fn track_address(&mut self, address: &str) -> anyhow::Result<()> {
+        if !self.addresses.iter().any(|(e, _)| e == address) {
+            self.addresses.push((address.to_string(), None));
+        }
+        Ok(())
+    }
+
+    fn track_mempool(&mut self) -> anyhow::Result<()> {
+        self.mempool = Some(None);
+        Ok(())
+    }
+
+    fn next_event(&mut self, timeout: Duration) -> anyhow::Result<Option<TxEvent>> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Ok(Some(event));
+            }
+            let now = Instant::now();
+            if now >= self.next_poll {
+                self.next_poll = now + POLL_INTERVAL;
+                self.poll()?;
+                continue;
+            }
+            if now >= deadline {
+                return Ok(None);
+            }
+            thread::sleep(self.next_poll.min(deadline) - now);
+        }
+    }
+}
@@ -0,0 +1,371 @@
+use std::{collections::VecDeque, io, net::TcpStream, time::Duration};
+
+use anyhow::bail;
+use bitcoin::Network;
+use serde_json::{json, Value};
+use tungstenite::{stream::MaybeTlsStream, Message, WebSocket};
+
+use super::{TxEvent, TxTracker};
+
+/// mempool.space websocket of `network`, there is none for regtest
+pub(crate) fn url(network: Network) -> anyhow::Result<String> {
+    Ok(match network {
+        Network::Bitcoin => "wss://mempool.space/api/v1/ws".to_string(),
+        Network::Testnet => "wss://mempool.space/testnet/api/v1/ws".to_string(),
+        Network::Signet => "wss://mempool.space/signet/api/v1/ws".to_string(),
+        _ => bail!(
+            "No mempool.space websocket for {}, set ws_api or zmq_api",
+            network
+        ),
+    })
+}
+
+/// mempool.space websocket, one tracked tx per connection
+pub struct Client {
+    socket: WebSocket<MaybeTlsStream<TcpStream>>,
+    txid: Option<String>,
+    addresses: Vec<String>,
+    events: VecDeque<TxEvent>,
+}
+
+impl Client {
+    pub fn connect(url: &str) -> anyhow::Result<Self> {
+        let (socket, _) = tungstenite::connect(url)?;
+        let mut client = Self {
+            socket,
+            txid: None,
+            addresses: Vec::new(),
+            events: VecDeque::new(),
+        };
+        // confirmations come with blocks
+        client.send(json!({"action": "want", "data": ["blocks"]}))?;
+        Ok(client)
+    }
+
+    /// Replacements of any tx in the mempool
+    pub fn track_rbf(&mut self) -> anyhow::Result<()> {
+        self.send(json!({"track-rbf": "all"}))
+    }
+
+    fn send(&mut self, value: Value) -> anyhow::Result<()> {
+        self.socket.send(Message::text(value.to_string()))?;
+        Ok(())
+    }
+
+    fn set_timeout(&mut self, timeout: Duration) -> anyhow::Result<()> {
+        match self.socket.get_mut() {
+            MaybeTlsStream::Plain(stream) => stream.set_read_timeout(Some(timeout))?,
+            MaybeTlsStream::NativeTls(stream) => {
+                stream.get_mut().set_read_timeout(Some(timeout))?
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn parse(&mut self, text: &str) -> anyhow::Result<()> {
+        let value = serde_json::from_str::<Value>(text)?;
+        if let (Some(txid), Some(replaced)) = (&self.txid, value.get("txReplaced")) {
+            if let Some(by) = replaced["txid"].as_str() {
+                self.events.push_back(TxEvent::Replaced {
+                    txid: txid.clone(),
+                    by: by.to_string(),
+                });
+            }
+        }
+        if let Some(confirmed) = value.get("txConfirmed") {
+            let txid = confirmed
+                .as_str()
+                .map(|e| e.to_string())
+                .or(self.txid.clone());
+            if let Some(txid) = txid {
+                self.events.push_back(TxEvent::Confirmed {
+                    txid,
+                    block_height: value["block"]["height"].as_u64(),
+                });
+            }
+        }
+        // {address: {mempool: [tx], confirmed: [tx], removed: [tx]}}
+        for (address, txs) in value["multi-address-transactions"]
+            .as_object()
+            .into_iter()
+            .flatten()
+        {
+            for tx in txs["mempool"].as_array().into_iter().flatten() {
+                if let Some(txid) = tx["txid"].as_str() {
+                    self.events.push_back(TxEvent::AddressTx {
+                        address: address.clone(),
+                        txid: txid.to_string(),
+                    });
+                }
+            }
+        }
+        for txid in value["mempool-txids"]["added"]
+            .as_array()
+            .into_iter()
+            .flatten()
+        {
+            if let Some(txid) = txid.as_str() {
+                self.events.push_back(TxEvent::MempoolTx {
+                    txid: txid.to_string(),
+                });
+            }
+        }
+        if let Some(trees) = value["rbfLatest"].as_array() {
+            for tree in trees {
+                push_replaced(&mut self.events, tree);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `{tx: {txid}, replaces: [tree]}`, every child is replaced by its parent
+fn push_replaced(events: &mut VecDeque<TxEvent>, tree: &Value) {
+    let Some(by) = tree["tx"]["txid"].as_str() else {
+        return;
+    };
+    for child in tree["replaces"].as_array().into_iter().flatten() {
+        if let Some(txid) = child["tx"]["txid"].as_str() {
+            events.push_back(TxEvent::Replaced {
+                txid: txid.to_string(),
+                by: by.to_string(),
+            });
+        }
+        push_replaced(events, child);
+    }
+}
+
+impl TxTracker for Client {
+    fn track_tx(&mut self, txid: &str) -> anyhow::Result<()> {
+        self.txid = Some(txid.to_string());
+        self.send(json!({"track-tx": txid}))
+    }
+
+    fn track_address(&mut self, address: &str) -> anyhow::Result<()> {
+        if !self.addresses.iter().any(|e| e == address) {
+            self.addresses.push(address.to_string());
+        }
+        // the whole list replaces the previous one
+        self.send(json!({"track-addresses": self.addresses}))
+    }
+
+    fn track_mempool(&mut self) -> anyhow::Result<()> {
+        self.send(json!({"track-mempool-txids": true}))
+    }
+
+    fn next_event(&mut self, timeout: Duration) -> anyhow::Result<Option<TxEvent>> {
+        self.set_timeout(timeout)?;
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Ok(Some(event));
+            }
+            match self.socket.read() {
+                Ok(Message::Text(text)) => self.parse(&text)?,
+                Ok(_) => {}
+                Err(tungstenite::Error::Io(e))
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                    ) =>
+                {
+                    return Ok(None)
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::TcpListener, thread};
+
+    use super::*;
+    use crate::{
+        btc_api::esplora,
+        tracker::{poll, Reconnect},
+    };
+
+    /// Accept one client, check its first messages then push `messages`
+    fn mock_server(expected: Vec<Value>, messages: Vec<Value>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut socket = tungstenite::accept(stream).unwrap();
+            for expected in expected {
+                let message = socket.read().unwrap().into_text().unwrap();
+                assert_eq!(serde_json::from_str::<Value>(&message).unwrap(), expected);
+            }
+            for message in messages {
+                socket.send(Message::text(message.to_string())).unwrap();
+            }
+            // keep the connection open for the timeout test
+            let _ = socket.read();
+        });
+        format!("ws://{}", addr)
+    }
+
+    fn want() -> Value {
+        json!({"action": "want", "data": ["blocks"]})
+    }
+
+    #[test]
+    fn test_track_tx() {
+        let url = mock_server(
+            vec![want(), json!({"track-tx": "a"})],
+            vec![
+                json!({"txReplaced": {"txid": "b"}}),
+                json!({"txConfirmed": "a", "block": {"height": 840000}}),
+            ],
+        );
+        let mut client = Client::connect(&url).unwrap();
+        client.track_tx("a").unwrap();
+        let timeout = Duration::from_secs(5);
+        assert_eq!(
+            client.next_event(timeout).unwrap(),
+            Some(TxEvent::Replaced {
+                txid: "a".to_string(),
+                by: "b".to_string()
+            })
+        );
+        assert_eq!(
+            client.next_event(timeout).unwrap(),
+            Some(TxEvent::Confirmed {
+                txid: "a".to_string(),
+                block_height: Some(840000)
+            })
+        );
+        assert_eq!(client.next_event(Duration::from_millis(200)).unwrap(), None);
+    }
+
+    #[test]
+    fn test_track_address_and_rbf() {
+        let url = mock_server(
+            vec![
+                want(),
+                json!({"track-addresses": ["bc1q"]}),
+                json!({"track-addresses": ["bc1q", "bc1p"]}),
+                json!({"track-rbf": "all"}),
+            ],
+            vec![
+                json!({"multi-address-transactions": {
+                    "bc1q": {"mempool": [{"txid": "a"}, {"txid": "b"}], "confirmed": [], "removed": []}
+                }}),
+                json!({"rbfLatest": [{"tx": {"txid": "c"}, "replaces": [
+                    {"tx": {"txid": "b"}, "replaces": [{"tx": {"txid": "a"}, "replaces": []}]}
+                ]}]}),
+            ],
+        );
+        let mut client = Client::connect(&url).unwrap();
+        client.track_address("bc1q").unwrap();
+        client.track_address("bc1p").unwrap();
+        client.track_rbf().unwrap();
+        let mut events = Vec::new();
+        while let Some(event) = client.next_event(Duration::from_secs(1)).unwrap() {
+            events.push(event);
+        }
+        let address_tx = |txid: &str| TxEvent::AddressTx {
+            address: "bc1q".to_string(),
+            txid: txid.to_string(),
+        };
+        assert_eq!(
+            events,
+            vec![
+                address_tx("a"),
+                address_tx("b"),
+                TxEvent::Replaced {
+                    txid: "b".to_string(),
+                    by: "c".to_string()
+                },
+                TxEvent::Replaced {
+                    txid: "a".to_string(),
+                    by: "b".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_track_mempool() {
+        let url = mock_server(
+            vec![want(), json!({"track-mempool-txids": true})],
+            vec![json!({"mempool-txids": {"sequence": 1, "added": ["a", "b"], "removed": []}})],
+        );
+        let mut client = Client::connect(&url).unwrap();
+        client.track_mempool().unwrap();
+        let timeout = Duration::from_secs(5);
+        for txid in ["a", "b"] {
+            assert_eq!(
+                client.next_event(timeout).unwrap(),
+                Some(TxEvent::MempoolTx {
+                    txid: txid.to_string()
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn test_reconnect() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            let read = |socket: &mut WebSocket<TcpStream>| {
+                serde_json::from_str::<Value>(&socket.read().unwrap().into_text().unwrap()).unwrap()
+            };
+            // dropped after the subscription
+            let mut socket = tungstenite::accept(listener.accept().unwrap().0).unwrap();
+            assert_eq!(read(&mut socket), want());
+            assert_eq!(read(&mut socket), json!({"track-tx": "a"}));
+            drop(socket);
+
+            let mut socket = tungstenite::accept(listener.accept().unwrap().0).unwrap();
+            assert_eq!(read(&mut socket), want());
+            // replayed
+            assert_eq!(read(&mut socket), json!({"track-tx": "a"}));
+            socket
+                .send(Message::text(
+                    json!({"txConfirmed": "a", "block": {"height": 840000}}).to_string(),
+                ))
+                .unwrap();
+            let _ = socket.read();
+        });
+        // nothing listens, polls fail while disconnected
+        let fallback = poll::Client::new(esplora::new_with_custom_url(
+            Network::Bitcoin,
+            "http://127.0.0.1:1",
+        ));
+        let mut tracker =
+            Reconnect::new(move || Ok(Box::new(Client::connect(&url)?)), fallback).unwrap();
+        tracker.track_tx("a").unwrap();
+        assert_eq!(
+            tracker.next_event(Duration::from_secs(10)).unwrap(),
+            Some(TxEvent::Confirmed {
+                txid: "a".to_string(),
+                block_height: Some(840000)
+            })
+        );
+    }
+
+    #[test]
+    fn test_url() {
+        assert!(url(Network::Signet).unwrap().contains("/signet/"));
+        assert!(url(Network::Regtest).is_err());
+    }
+
+    #[test]
+    fn test_wait_tx() {
+        let url = mock_server(
+            vec![want(), json!({"track-tx": "a"})],
+            vec![json!({"txReplaced": {"txid": "b"}})],
+        );
+        let mut client = Client::connect(&url).unwrap();
+        assert_eq!(
+            crate::tracker::wait_tx(&mut client, "a", Duration::from_secs(5)).unwrap(),
+            crate::tracker::TxOutcome::Replaced {
+                by: "b".to_string()
+            }
+        );
+    }
+}
@@ -0,0 +1,366 @@
+use std::{
+    collections::{BTreeSet, HashMap, HashSet, VecDeque},
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use bitcoin::{consensus::encode, Address, Network, OutPoint, ScriptBuf, Transaction, Txid};
+
+use super::{TxEvent, TxTracker};
+use crate::btc_api::btc_json_rpc;
+
+/// bitcoind `zmqpubrawtx` + `zmqpubsequence` subscriber
+pub struct Client {
+    socket: ::zmq::Socket,
+    network: Network,
+    rpc: Option<btc_json_rpc::Client>,
+    txid: Option<Txid>,
+    /// Inputs of the tracked tx, anything else spending them replaced it
+    spent: HashSet<OutPoint>,
+    addresses: Vec<(String, ScriptBuf)>,
+    /// Outputs seen paying a tracked address, by its index
+    funded: HashMap<OutPoint, usize>,
+    mempool: bool,
+    /// rawtx waiting for its `A`, block txs are republished and never get one
+    pending: HashMap<Txid, Transaction>,
+    /// `A` waiting for its rawtx
+    added: HashSet<Txid>,
+    events: VecDeque<TxEvent>,
+}
+
+impl Client {
+    /// `rpc` is needed to tell when the tracked tx is confirmed
+    pub fn connect(
+        endpoint: &str,
+        network: Network,
+        rpc: Option<btc_json_rpc::Client>,
+    ) -> anyhow::Result<Self> {
+        let socket = ::zmq::Context::new().socket(::zmq::SUB)?;
+        socket.connect(endpoint)?;
+        socket.set_subscribe(b"rawtx")?;
+        socket.set_subscribe(b"sequence")?;
+        Ok(Self {
+            socket,
+            network,
+            rpc,
+            txid: None,
+            spent: HashSet::new(),
+            addresses: Vec::new(),
+            funded: HashMap::new(),
+            mempool: false,
+            pending: HashMap::new(),
+            added: HashSet::new(),
+            events: VecDeque::new(),
+        })
+    }
+
+    /// `[topic, body, seq]`
+    fn handle(&mut self, parts: &[Vec<u8>]) -> anyhow::Result<()> {
+        let [topic, body, ..] = parts else {
+            return Ok(());
+        };
+        match topic.as_slice() {
+            b"rawtx" => {
+                let tx: Transaction = encode::deserialize(body)?;
+                let txid = tx.txid();
+                if self.added.remove(&txid) {
+                    self.handle_tx(tx)?;
+                } else {
+                    self.pending.insert(txid, tx);
+                }
+            }
+            // hash + label (+ mempool sequence)
+            b"sequence" => {
+                let (Some(hash), Some(label)) = (body.get(..32), body.get(32)) else {
+                    return Ok(());
+                };
+                match label {
+                    b'A' => {
+                        let txid = Txid::from_str(&hex::encode(hash))?;
+                        match self.pending.remove(&txid) {
+                            Some(tx) => self.handle_tx(tx)?,
+                            None => {
+                                self.added.insert(txid);
+                            }
+                        }
+                    }
+                    b'R' => {
+                        let txid = Txid::from_str(&hex::encode(hash))?;
+                        self.pending.remove(&txid);
+                        self.added.remove(&txid);
+                    }
+                    // connected block, its txs came through rawtx without `A`
+                    b'C' => {
+                        self.pending.clear();
+                        self.check_confirmed()?;
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// A tx entering the mempool
+    fn handle_tx(&mut self, tx: Transaction) -> anyhow::Result<()> {
+        let txid = tx.txid();
+        if let Some(tracked) = self.txid {
+            if txid == tracked {
+                if self.spent.is_empty() {
+                    self.spent = tx.input.iter().map(|e| e.previous_output).collect();
+                }
+            } else if tx
+                .input
+                .iter()
+                .any(|e| self.spent.contains(&e.previous_output))
+            {
+                self.events.push_back(TxEvent::Replaced {
+                    txid: tracked.to_string(),
+                    by: txid.to_string(),
+                });
+            }
+        }
+        // paying or spending a tracked address
+        let mut touched = BTreeSet::new();
+        for (vout, output) in tx.output.iter().enumerate() {
+            if let Some(i) = self.address_index(&output.script_pubkey) {
+                self.funded.insert(OutPoint::new(txid, vout as u32), i);
+                touched.insert(i);
+            }
+        }
+        if !self.addresses.is_empty() {
+            for input in &tx.input {
+                if let Some(i) = self.funding_address(&input.previous_output)? {
+                    touched.insert(i);
+                }
+            }
+        }
+        for i in touched {
+            self.events.push_back(TxEvent::AddressTx {
+                address: self.addresses[i].0.clone(),
+                txid: txid.to_string(),
+            });
+        }
+        if self.mempool {
+            self.events.push_back(TxEvent::MempoolTx {
+                txid: txid.to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    fn address_index(&self, script: &ScriptBuf) -> Option<usize> {
+        self.addresses.iter().position(|(_, e)| e == script)
+    }
+
+    /// The tracked address `out_point` pays, from outputs seen so far or the chain through rpc
+    fn funding_address(&self, out_point: &OutPoint) -> anyhow::Result<Option<usize>> {
+        if let Some(i) = self.funded.get(out_point) {
+            return Ok(Some(*i));
+        }
+        let Some(rpc) = &self.rpc else {
+            return Ok(None);
+        };
+        let script = rpc.get_tx_out_script(out_point)?;
+        Ok(script.and_then(|e| self.address_index(&e)))
+    }
+
+    fn check_confirmed(&mut self) -> anyhow::Result<()> {
+        let (Some(txid), Some(rpc)) = (self.txid, &self.rpc) else {
+            return Ok(());
+        };
+        let tx = rpc.get_tx(&txid.to_string())?;
+        if tx.confirmations.unwrap_or_default() > 0 {
+            self.events.push_back(TxEvent::Confirmed {
+                txid: txid.to_string(),
+                block_height: None,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl TxTracker for Client {
+    fn track_tx(&mut self, txid: &str) -> anyhow::Result<()> {
+        self.txid = Some(Txid::from_str(txid)?);
+        self.spent.clear();
+        // already in the mempool, no rawtx is coming for it
+        if let Some(rpc) = &self.rpc {
+            let tx: Transaction = encode::deserialize(&hex::decode(rpc.get_raw_tx(txid)?)?)?;
+            self.spent = tx.input.iter().map(|e| e.previous_output).collect();
+        }
+        Ok(())
+    }
+
+    fn track_address(&mut self, address: &str) -> anyhow::Result<()> {
+        let script = Address::from_str(address)?
+            .require_network(self.network)?
+            .script_pubkey();
+        if !self.addresses.iter().any(|(e, _)| e == address) {
+            self.addresses.push((address.to_string(), script));
+        }
+        Ok(())
+    }
+
+    fn track_mempool(&mut self) -> anyhow::Result<()> {
+        self.mempool = true;
+        Ok(())
+    }
+
+    fn next_event(&mut self, timeout: Duration) -> anyhow::Result<Option<TxEvent>> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Ok(Some(event));
+            }
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() || self.socket.poll(::zmq::POLLIN, left.as_millis() as i64)? == 0 {
+                return Ok(None);
+            }
+            let parts = self.socket.recv_multipart(0)?;
+            self.handle(&parts)?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use bitcoin::{
+        absolute::LockTime, hashes::Hash, transaction::Version, Amount, Sequence, TxIn, TxOut,
+        Witness,
+    };
+
+    use super::*;
+
+    const ADDRESS: &str = "bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr";
+
+    fn tx(previous_output: OutPoint, value: u64) -> Transaction {
+        let script_pubkey = Address::from_str(ADDRESS)
+            .unwrap()
+            .assume_checked()
+            .script_pubkey();
+        Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output,
+                script_sig: Default::default(),
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                witness: Witness::new(),
+            }],
+            output: vec![TxOut {
+                value: Amount::from_sat(value),
+                script_pubkey,
+            }],
+        }
+    }
+
+    /// rawtx followed by its mempool acceptance
+    fn accepted(tx: &Transaction) -> Vec<(&'static str, Vec<u8>)> {
+        vec![
+            ("rawtx", encode::serialize(tx)),
+            ("sequence", sequence(tx.txid(), b'A')),
+        ]
+    }
+
+    /// Hash in rpc byte order, label and mempool sequence
+    fn sequence(txid: Txid, label: u8) -> Vec<u8> {
+        [
+            hex::decode(txid.to_string()).unwrap(),
+            vec![label],
+            0u64.to_le_bytes().to_vec(),
+        ]
+        .concat()
+    }
+
+    /// Bound PUB socket publishing `messages` after the subscriber joined
+    fn publisher(messages: Vec<(&'static str, Vec<u8>)>) -> String {
+        let socket = ::zmq::Context::new().socket(::zmq::PUB).unwrap();
+        socket.bind("tcp://127.0.0.1:*").unwrap();
+        let endpoint = socket.get_last_endpoint().unwrap().unwrap();
+        thread::spawn(move || {
+            // slow joiner
+            thread::sleep(Duration::from_millis(300));
+            for (seq, (topic, body)) in messages.into_iter().enumerate() {
+                let seq = (seq as u32).to_le_bytes();
+                socket
+                    .send_multipart([topic.as_bytes(), body.as_slice(), seq.as_slice()], 0)
+                    .unwrap();
+            }
+            thread::sleep(Duration::from_secs(2));
+        });
+        endpoint
+    }
+
+    #[test]
+    fn test_replaced() {
+        let out_point = OutPoint::new(Txid::all_zeros(), 0);
+        let origin = tx(out_point, 1000);
+        let replacement = tx(out_point, 900);
+        let endpoint = publisher(
+            [
+                accepted(&origin),
+                vec![("sequence", [[0; 32].as_slice(), b"C".as_slice()].concat())],
+                accepted(&replacement),
+            ]
+            .concat(),
+        );
+        let mut client = Client::connect(&endpoint, Network::Bitcoin, None).unwrap();
+        client.track_tx(&origin.txid().to_string()).unwrap();
+        assert_eq!(
+            client.next_event(Duration::from_secs(5)).unwrap(),
+            Some(TxEvent::Replaced {
+                txid: origin.txid().to_string(),
+                by: replacement.txid().to_string()
+            })
+        );
+        assert_eq!(client.next_event(Duration::from_millis(200)).unwrap(), None);
+    }
+
+    #[test]
+    fn test_track_address() {
+        let funding = tx(OutPoint::new(Txid::all_zeros(), 1), 1000);
+        let mut spending = tx(OutPoint::new(funding.txid(), 0), 900);
+        spending.output[0].script_pubkey = ScriptBuf::new();
+        let endpoint = publisher([accepted(&funding), accepted(&spending)].concat());
+        let mut client = Client::connect(&endpoint, Network::Bitcoin, None).unwrap();
+        client.track_address(ADDRESS).unwrap();
+        for tx in [&funding, &spending] {
+            assert_eq!(
+                client.next_event(Duration::from_secs(5)).unwrap(),
+                Some(TxEvent::AddressTx {
+                    address: ADDRESS.to_string(),
+                    txid: tx.txid().to_string()
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn test_track_mempool() {
+        let tx = tx(OutPoint::new(Txid::all_zeros(), 2), 1000);
+        // accepted, then republished by the block confirming it
+        let endpoint = publisher(
+            [
+                accepted(&tx),
+                vec![
+                    ("rawtx", encode::serialize(&tx)),
+                    ("sequence", [[0; 32].as_slice(), b"C".as_slice()].concat()),
+                ],
+            ]
+            .concat(),
+        );
+        let mut client = Client::connect(&endpoint, Network::Bitcoin, None).unwrap();
+        client.track_mempool().unwrap();
+        assert_eq!(
+            client.next_event(Duration::from_secs(5)).unwrap(),
+            Some(TxEvent::MempoolTx {
+                txid: tx.txid().to_string()
+            })
+        );
+        assert_eq!(client.next_event(Duration::from_secs(1)).unwrap(), None);
+    }
+}
@@ -1,9 +1,12 @@
-use std::str::FromStr;
+use std::{fs, path::Path, str::FromStr};
 
 use bitcoin::{Address, Network, Psbt, TxIn, TxOut};
 use console_utils::input::select;
 
-use crate::btc_api::{esplora::Prevout, ordinal::Ordinal};
+use crate::{
+    btc_api::{esplora::Prevout, ordinal::Ordinal},
+    wallet::Wallet,
+};
 
 pub(crate) fn print_snipe_table(
     signed_psbt: &Psbt,
@@ -121,19 +124,26 @@ pub(crate) fn print_table(psbt: &Psbt, network: Network) {
         .iter()
         .map(|e| format!("{}", e.previous_output))
         .collect::<Vec<_>>();
-    inputs.resize(psbt.unsigned_tx.output.len(), "".to_string());
-    let outputs = psbt
+    let mut outputs = psbt
         .unsigned_tx
         .output
         .iter()
-        .map(|e| {
-            format!(
-                "{}\n{}",
-                Address::from_script(e.script_pubkey.as_script(), network).unwrap(),
-                e.value
-            )
-        })
+        .map(
+            |e| match Address::from_script(e.script_pubkey.as_script(), network) {
+                Ok(addr) => {
+                    format!("{}\n{}", addr, e.value)
+                }
+                Err(_) => {
+                    format!("{}", e.script_pubkey.to_asm_string())
+                }
+            },
+        )
         .collect::<Vec<_>>();
+    if inputs.len() < outputs.len() {
+        inputs.resize(outputs.len(), "".to_string());
+    } else {
+        outputs.resize(inputs.len(), "".to_string());
+    }
     let rows = inputs
         .into_iter()
         .zip(outputs.into_iter())
@@ -153,6 +163,40 @@ pub(crate) fn select_confirm(msg: &str) -> bool {
     let selected = select(msg, &["Yes", "No"]);
     selected == 0
 }
+
+/// Watch-only wallets stop here, the unsigned psbt goes to `<txid>.psbt` in base64 with the
+/// key origins of `wallet`
+pub(crate) fn export_psbt(
+    wallet: &dyn Wallet,
+    psbt: &Psbt,
+    network: Network,
+) -> anyhow::Result<()> {
+    let mut psbt = psbt.clone();
+    wallet.update_psbt(&mut psbt)?;
+    print_table(&psbt, network);
+    let path = format!("{}.psbt", psbt.unsigned_tx.txid());
+    fs::write(&path, psbt.to_string())?;
+    println!("{}", psbt);
+    log::info!(
+        "[watch-only] Unsigned psbt saved to {}, sign it then run finalize",
+        path
+    );
+    Ok(())
+}
+
+/// Psbt in base64 or hex, or a file containing either
+pub(crate) fn read_psbt(psbt: &str) -> anyhow::Result<Psbt> {
+    let content = if Path::new(psbt).is_file() {
+        fs::read_to_string(psbt)?
+    } else {
+        psbt.to_string()
+    };
+    let content = content.trim();
+    match hex::decode(content) {
+        Ok(bytes) => Ok(Psbt::deserialize(&bytes)?),
+        Err(_) => Ok(Psbt::from_str(content)?),
+    }
+}
 #[cfg(test)]
 mod tests {
     use std::str::FromStr;
@@ -168,4 +212,18 @@ mod tests {
         )
         .unwrap();
     }
+
+    #[test]
+    fn test_read_psbt() {
+        let tx = bitcoin::Transaction {
+            version: bitcoin::transaction::Version::TWO,
+            lock_time: bitcoin::absolute::LockTime::ZERO,
+            input: vec![Default::default()],
+            output: vec![],
+        };
+        let psbt = bitcoin::Psbt::from_unsigned_tx(tx).unwrap();
+        assert_eq!(super::read_psbt(&psbt.serialize_hex()).unwrap(), psbt);
+        assert_eq!(super::read_psbt(&psbt.to_string()).unwrap(), psbt);
+        assert!(super::read_psbt("cHNidP8").is_err());
+    }
 }
@@ -0,0 +1,140 @@
+use anyhow::{anyhow, bail};
+use bitcoin::{consensus::encode, Psbt, Transaction, TxOut, Witness};
+
+use crate::sighash::decode_sighash;
+
+/// [`verify_tx`] with the prevouts from the psbt witness utxos
+pub(crate) fn verify_psbt(psbt: &Psbt, signed_tx: &Transaction) -> anyhow::Result<()> {
+    let prevouts = psbt
+        .inputs
+        .iter()
+        .enumerate()
+        .map(|(i, e)| {
+            e.witness_utxo
+                .clone()
+                .ok_or(anyhow!("Input {}: missing witness utxo", i))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    verify_tx(signed_tx, &prevouts)
+}
+
+/// Script check every input of a signed tx the way nodes do, `prevouts` in input order.
+pub(crate) fn verify_tx(tx: &Transaction, prevouts: &[TxOut]) -> anyhow::Result<()> {
+    if tx.input.len() != prevouts.len() {
+        bail!("{} inputs but {} prevouts", tx.input.len(), prevouts.len());
+    }
+    let tx_bytes = encode::serialize(tx);
+    let spent_outputs = prevouts
+        .iter()
+        .map(|e| bitcoinconsensus::Utxo {
+            script_pubkey: e.script_pubkey.as_bytes().as_ptr(),
+            script_pubkey_len: e.script_pubkey.len() as u32,
+            value: e.value.to_sat() as i64,
+        })
+        .collect::<Vec<_>>();
+
+    let mut errors = Vec::new();
+    for (i, (input, prevout)) in tx.input.iter().zip(prevouts).enumerate() {
+        let result = bitcoinconsensus::verify_with_flags(
+            prevout.script_pubkey.as_bytes(),
+            prevout.value.to_sat(),
+            &tx_bytes,
+            Some(&spent_outputs),
+            i,
+            bitcoinconsensus::VERIFY_ALL_PRE_TAPROOT | bitcoinconsensus::VERIFY_TAPROOT,
+        );
+        if let Err(e) = result {
+            let mut error = format!("Input {} ({}): {:?}", i, input.previous_output, e);
+            if let Some(hint) = sighash_hint(&input.witness, prevout) {
+                error = format!("{}, {}", error, hint);
+            }
+            errors.push(error);
+        }
+    }
+    if !errors.is_empty() {
+        bail!("Script verification failed\n{}", errors.join("\n"));
+    }
+    Ok(())
+}
+
+/// Signatures that can not be lifted commit to the original tx layout
+fn sighash_hint(witness: &Witness, prevout: &TxOut) -> Option<String> {
+    match decode_sighash(witness, &prevout.script_pubkey) {
+        Some(sighash) if !sighash.liftable() => Some(format!(
+            "signed with {}, the signature does not commit to our outputs",
+            sighash
+        )),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::{
+        absolute::LockTime,
+        hashes::Hash,
+        key::{Keypair, TapTweak},
+        secp256k1::{rand, Message, Secp256k1},
+        sighash::{Prevouts, SighashCache},
+        taproot,
+        transaction::Version,
+        Address, Amount, Network, OutPoint, Sequence, TapSighashType, TxIn,
+    };
+
+    use super::*;
+
+    fn signed_tx(hash_ty: TapSighashType) -> (Transaction, Vec<TxOut>) {
+        let secp = Secp256k1::new();
+        let keypair = Keypair::new(&secp, &mut rand::thread_rng());
+        let (internal_key, _) = keypair.x_only_public_key();
+        let addr = Address::p2tr(&secp, internal_key, None, Network::Bitcoin);
+        let prevouts = vec![TxOut {
+            value: Amount::from_sat(10_000),
+            script_pubkey: addr.script_pubkey(),
+        }];
+        let mut tx = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::null(),
+                script_sig: Default::default(),
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                witness: Default::default(),
+            }],
+            output: vec![TxOut {
+                value: Amount::from_sat(9_000),
+                script_pubkey: addr.script_pubkey(),
+            }],
+        };
+        let hash = SighashCache::new(&tx)
+            .taproot_key_spend_signature_hash(0, &Prevouts::All(&prevouts), hash_ty)
+            .unwrap();
+        let signature = taproot::Signature {
+            sig: secp.sign_schnorr(
+                &Message::from_digest(hash.to_byte_array()),
+                &keypair.tap_tweak(&secp, None).to_inner(),
+            ),
+            hash_ty,
+        };
+        tx.input[0].witness = Witness::from_slice(&[signature.to_vec()]);
+        (tx, prevouts)
+    }
+
+    #[test]
+    fn test_verify_tx() {
+        let (mut tx, prevouts) = signed_tx(TapSighashType::SinglePlusAnyoneCanPay);
+        verify_tx(&tx, &prevouts).unwrap();
+        // other outputs are not committed
+        tx.output.push(tx.output[0].clone());
+        verify_tx(&tx, &prevouts).unwrap();
+        tx.output[0].value = Amount::from_sat(8_000);
+        assert!(verify_tx(&tx, &prevouts).is_err());
+
+        let (mut tx, prevouts) = signed_tx(TapSighashType::All);
+        verify_tx(&tx, &prevouts).unwrap();
+        tx.output.push(tx.output[0].clone());
+        let error = verify_tx(&tx, &prevouts).unwrap_err().to_string();
+        assert!(error.contains("Input 0"));
+        assert!(error.contains("SIGHASH_ALL"));
+    }
+}
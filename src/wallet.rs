@@ -1,133 +1,340 @@
 use std::str::FromStr;
 
+use anyhow::bail;
 use bdk::{
     chain::{local_chain::CheckPoint, spk_client::SyncRequest},
-    keys::{DerivableKey, ExtendedKey},
-    template, KeychainKind, SignOptions,
+    KeychainKind, SignOptions,
 };
 use bip39::Mnemonic;
-use bitcoin::Psbt;
+use bitcoin::{bip32::Xpriv, PrivateKey, Psbt};
 use miniscript::{
     bitcoin::{
         secp256k1::{All, Secp256k1},
         Address, Network,
     },
-    Tap, ToPublicKey,
+    ToPublicKey,
 };
 use serde::Deserialize;
 
-use crate::default;
+use crate::{btc_api::esplora, default, setting::Settings};
 
+/// Browser wallet layouts, the account is an address index or a bip32 account depending on the wallet
 #[derive(Debug, Deserialize, Default, Copy, Clone, PartialEq, Eq)]
-#[serde(rename_all = "lowercase")]
+#[serde(rename_all = "kebab-case")]
 pub(crate) enum Mode {
+    /// One taproot address for pay and ordi
     #[default]
-    Other,
+    #[serde(alias = "other")]
+    Unisat,
+    /// One native segwit address for pay and ordi
+    UnisatSegwit,
+    /// Nested segwit pay, taproot ordi
+    #[serde(rename = "xverse")]
     XVerse,
+    /// Native segwit pay, taproot ordi
+    Leather,
+    /// Same layout as Leather
+    Okx,
+    /// Same layout as Leather
+    MagicEden,
+    /// Taproot pay at index 0, ordi at index 1
     Peek,
 }
 
-pub(crate) struct MnemonicWallet {
-    pub(crate) pay_wallet: bdk::Wallet,
-    ordi_wallet: bdk::Wallet,
-    mode: Mode,
-    network: Network,
+impl Mode {
+    pub(crate) const ALL: [Mode; 7] = [
+        Mode::Unisat,
+        Mode::UnisatSegwit,
+        Mode::XVerse,
+        Mode::Leather,
+        Mode::Okx,
+        Mode::MagicEden,
+        Mode::Peek,
+    ];
+
+    /// (script, bip32 account, address index) of pay and ordi for `account`
+    fn layout(&self, account: u32) -> [(ScriptType, u32, u32); 2] {
+        use ScriptType::*;
+        match self {
+            Mode::Unisat => [(Tr, 0, account), (Tr, 0, account)],
+            Mode::UnisatSegwit => [(Wpkh, 0, account), (Wpkh, 0, account)],
+            Mode::XVerse => [(ShWpkh, 0, account), (Tr, 0, account)],
+            Mode::Leather | Mode::Okx | Mode::MagicEden => [(Wpkh, account, 0), (Tr, account, 0)],
+            Mode::Peek => [(Tr, 0, account), (Tr, 0, account + 1)],
+        }
+    }
 }
 
-impl MnemonicWallet {
-    pub(crate) fn new(mnemonic: &str, mode: Mode, network: Network) -> anyhow::Result<Self> {
-        match mode {
-            Mode::Other => Self::new_mnemonic_with_mode(mnemonic, network, mode),
-            Mode::XVerse => Self::new_mnemonic_by_xverse(mnemonic, network),
-            Mode::Peek => Self::new_mnemonic_with_mode(mnemonic, network, mode),
+#[derive(Debug, Deserialize, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum ScriptType {
+    Tr,
+    Wpkh,
+    ShWpkh,
+    Pkh,
+}
+
+impl ScriptType {
+    fn purpose(&self) -> u32 {
+        match self {
+            ScriptType::Tr => 86,
+            ScriptType::Wpkh => 84,
+            ScriptType::ShWpkh => 49,
+            ScriptType::Pkh => 44,
         }
     }
 
-    pub(crate) fn check(&self) {
-        log::info!("[wallet] PayAddr: {} ", self.pay_addr().to_string());
-        log::info!("[wallet] OrdiAddr: {} ", self.ordi_addr().to_string());
+    fn wrap(&self, key: &str) -> String {
+        match self {
+            ScriptType::Tr => format!("tr({})", key),
+            ScriptType::Wpkh => format!("wpkh({})", key),
+            ScriptType::ShWpkh => format!("sh(wpkh({}))", key),
+            ScriptType::Pkh => format!("pkh({})", key),
+        }
     }
+}
 
-    pub(crate) fn new_mnemonic_with_mode(
-        mnemonic: &str,
+/// `pay_descriptor`/`ordi_descriptor` in the config
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub(crate) enum DescriptorConfig {
+    /// Full descriptor, `wpkh([fingerprint/84'/0'/0']xpub/0/*)`
+    Raw(String),
+    /// Derived from the mnemonic, `path` (`m/86'/0'/0'/0/*`) overrides the purpose/account default
+    Derived {
+        script: ScriptType,
+        #[serde(default)]
+        account: u32,
+        path: Option<String>,
+    },
+}
+
+impl DescriptorConfig {
+    pub(crate) fn descriptor(
+        &self,
+        mnemonic: Option<&str>,
         network: Network,
-        mode: Mode,
-    ) -> anyhow::Result<Self> {
-        let mnemonic = Mnemonic::parse(mnemonic)?;
-        let xkey: ExtendedKey<Tap> = mnemonic.into_extended_key().unwrap();
-        let xprv = xkey.into_xprv(network).unwrap();
+    ) -> anyhow::Result<String> {
+        let (script, account, path) = match self {
+            DescriptorConfig::Raw(descriptor) => return Ok(descriptor.clone()),
+            DescriptorConfig::Derived {
+                script,
+                account,
+                path,
+            } => (script, account, path),
+        };
+        let Some(mnemonic) = mnemonic else {
+            bail!(
+                "Please setting [mnemonic] to derive {:?} descriptors",
+                script
+            );
+        };
+        let seed = Mnemonic::parse(mnemonic)?.to_seed("");
+        let xprv = Xpriv::new_master(network, &seed)?;
+        let path = match path {
+            Some(path) => path.trim_start_matches("m/").to_string(),
+            None => {
+                let coin = if network == Network::Bitcoin { 0 } else { 1 };
+                format!("{}'/{}'/{}'/0/*", script.purpose(), coin, account)
+            }
+        };
+        Ok(script.wrap(&format!("{}/{}", xprv, path)))
+    }
+}
 
-        let ordi = bdk::Wallet::new(
-            template::Bip86(xprv, KeychainKind::External),
-            None,
-            (),
-            network,
-        )?;
+pub trait Wallet {
+    fn pay_addr(&self) -> Address;
+    fn ordi_addr(&self) -> Address;
+    /// Pay address at `index`, single key wallets have only one
+    fn peek_addr(&self, index: u32) -> Address;
+    fn ctx(&self) -> &Secp256k1<All>;
+    /// Sign inputs of the pay address
+    fn sign(&self, psbt: &mut Psbt) -> anyhow::Result<bool>;
+    /// Sign inputs of both the pay and the ordi address
+    fn sign_all(&self, psbt: &mut Psbt) -> anyhow::Result<bool>;
+    /// Sign ordi inputs with any sighash, for listings
+    fn sign_swap(&self, psbt: &mut Psbt) -> anyhow::Result<bool>;
+    fn finalize(&self, psbt: &mut Psbt) -> anyhow::Result<bool>;
 
-        let pay = bdk::Wallet::new(
-            template::Bip86(xprv, KeychainKind::External),
-            None,
-            (),
-            network,
-        )?;
-
-        return Ok(Self {
-            pay_wallet: pay,
-            ordi_wallet: ordi,
-            mode,
-            network,
-        });
+    /// Commands export the unsigned psbt instead of signing
+    fn is_watch_only(&self) -> bool {
+        false
     }
 
-    pub(crate) fn new_mnemonic_by_xverse(mnemonic: &str, network: Network) -> anyhow::Result<Self> {
-        let mnemonic = Mnemonic::parse(mnemonic)?;
-        let xkey: ExtendedKey<Tap> = mnemonic.into_extended_key().unwrap();
-        let xprv = xkey.into_xprv(network).unwrap();
+    /// Add key origins of our inputs and outputs so an offline signer finds its keys
+    fn update_psbt(&self, _psbt: &mut Psbt) -> anyhow::Result<()> {
+        Ok(())
+    }
 
-        let ordi = bdk::Wallet::new_no_persist(
-            template::Bip86(xprv, KeychainKind::External),
-            None,
-            network,
-        )?;
+    /// Addresses holding spendable utxos, pay_addr first
+    fn pay_addrs(&self) -> Vec<Address> {
+        vec![self.pay_addr()]
+    }
 
-        let pay = bdk::Wallet::new_no_persist(
-            template::Bip49(xprv, KeychainKind::External),
-            None,
-            network,
-        )?;
-
-        return Ok(Self {
-            pay_wallet: pay,
-            ordi_wallet: ordi,
-            mode: Mode::XVerse,
-            network,
-        });
+    /// First unused internal address once discovered, pay_addr otherwise
+    fn change_addr(&self) -> Address {
+        self.pay_addr()
     }
 
-    pub(crate) fn pay_addr(&self) -> Address {
-        self.pay_wallet
-            .peek_address(KeychainKind::External, 0)
-            .address
+    /// Used internal addresses and change_addr, empty without an internal keychain
+    fn change_addrs(&self) -> Vec<Address> {
+        vec![]
     }
 
-    pub(crate) fn peek_addr(&self, peek: u32) -> Address {
-        self.pay_wallet
-            .peek_address(KeychainKind::External, peek)
-            .address
+    /// Scan the pay keychains until `gap_limit` unused addresses in a row
+    fn discover(&mut self, _btc_api: &esplora::Client, _gap_limit: u32) -> anyhow::Result<()> {
+        Ok(())
     }
-    pub(crate) fn ordi_addr(&self) -> Address {
-        match self.mode {
-            Mode::Peek => {
-                self.ordi_wallet
-                    .peek_address(KeychainKind::External, 1)
-                    .address
+
+    fn check(&self) {
+        log::info!("[wallet] PayAddr: {} ", self.pay_addr().to_string());
+        log::info!("[wallet] OrdiAddr: {} ", self.ordi_addr().to_string());
+    }
+}
+
+/// `/0/*` swapped for `/1/*`, None if the descriptor has no such range
+fn change_descriptor(descriptor: &str) -> Option<String> {
+    let descriptor = descriptor.split('#').next()?;
+    let i = descriptor.rfind("/0/*")?;
+    Some(format!("{}/1/*{}", &descriptor[..i], &descriptor[i + 4..]))
+}
+
+/// Used pay addresses and the next change address
+#[derive(Default)]
+struct Discovered {
+    addrs: Vec<Address>,
+    /// Used internal addresses, also in `addrs`
+    internal: Vec<Address>,
+    change: Option<Address>,
+}
+
+impl Discovered {
+    /// `is_used` is asked for every address until `gap_limit` unused in a row,
+    /// used ones are revealed so the signers know them
+    fn scan(
+        wallet: &mut bdk::Wallet,
+        has_change: bool,
+        gap_limit: u32,
+        mut is_used: impl FnMut(&Address) -> anyhow::Result<bool>,
+    ) -> anyhow::Result<Self> {
+        let mut discovered = Self::default();
+        let keychains: &[KeychainKind] = if has_change {
+            &[KeychainKind::External, KeychainKind::Internal]
+        } else {
+            &[KeychainKind::External]
+        };
+        for &keychain in keychains {
+            if !wallet.get_descriptor_for_keychain(keychain).has_wildcard() {
+                continue;
             }
-            _ => {
-                self.ordi_wallet
-                    .peek_address(KeychainKind::External, 0)
-                    .address
+            let (mut index, mut gap, mut last_used) = (0, 0, None);
+            while gap < gap_limit {
+                let addr = wallet.peek_address(keychain, index).address;
+                if is_used(&addr)? {
+                    log::info!("[discover] {:?} {} {}", keychain, index, addr);
+                    if keychain == KeychainKind::Internal {
+                        discovered.internal.push(addr.clone());
+                    }
+                    discovered.addrs.push(addr);
+                    last_used = Some(index);
+                    gap = 0;
+                } else {
+                    gap += 1;
+                }
+                index += 1;
+            }
+            if let Some(last_used) = last_used {
+                wallet
+                    .reveal_addresses_to(keychain, last_used)
+                    .for_each(drop);
+            }
+            if keychain == KeychainKind::Internal {
+                let index = last_used.map_or(0, |e| e + 1);
+                discovered.change = Some(wallet.peek_address(keychain, index).address);
+            }
+        }
+        Ok(discovered)
+    }
+
+    fn by_esplora(
+        wallet: &mut bdk::Wallet,
+        has_change: bool,
+        btc_api: &esplora::Client,
+        gap_limit: u32,
+    ) -> anyhow::Result<Self> {
+        Self::scan(wallet, has_change, gap_limit, |addr| {
+            Ok(!btc_api.get_transactions(&addr.to_string())?.is_empty())
+        })
+    }
+
+    fn pay_addrs(&self, pay_addr: Address) -> Vec<Address> {
+        let mut addrs = vec![pay_addr];
+        for addr in &self.addrs {
+            if !addrs.contains(addr) {
+                addrs.push(addr.clone());
             }
         }
+        addrs
+    }
+
+    fn change_addrs(&self) -> Vec<Address> {
+        self.internal
+            .iter()
+            .cloned()
+            .chain(self.change.clone())
+            .collect()
+    }
+}
+
+fn sign_options() -> SignOptions {
+    SignOptions {
+        trust_witness_utxo: true,
+        ..default()
+    }
+}
+
+fn swap_sign_options() -> SignOptions {
+    SignOptions {
+        allow_all_sighashes: true,
+        ..sign_options()
+    }
+}
+
+pub(crate) struct MnemonicWallet {
+    pay_wallet: bdk::Wallet,
+    ordi_wallet: bdk::Wallet,
+    pay_index: u32,
+    ordi_index: u32,
+    has_change: bool,
+    discovered: Discovered,
+}
+
+impl MnemonicWallet {
+    pub(crate) fn new(
+        mnemonic: &str,
+        mode: Mode,
+        account: u32,
+        network: Network,
+    ) -> anyhow::Result<Self> {
+        let [pay, ordi] = mode.layout(account).map(|(script, account, index)| {
+            let descriptor = DescriptorConfig::Derived {
+                script,
+                account,
+                path: None,
+            }
+            .descriptor(Some(mnemonic), network)?;
+            anyhow::Ok((descriptor, index))
+        });
+        let ((pay, pay_index), (ordi, ordi_index)) = (pay?, ordi?);
+        let change = change_descriptor(&pay);
+        Ok(Self {
+            pay_wallet: bdk::Wallet::new_no_persist(&pay, change.as_deref(), network)?,
+            ordi_wallet: bdk::Wallet::new_no_persist(&ordi, None, network)?,
+            pay_index,
+            ordi_index,
+            has_change: change.is_some(),
+            discovered: Discovered::default(),
+        })
     }
 
     pub(crate) fn sign_with_options(
@@ -137,191 +344,495 @@ impl MnemonicWallet {
     ) -> anyhow::Result<bool> {
         Ok(self.pay_wallet.sign(psbt, sign_options)?)
     }
+}
 
-    pub(crate) fn sign(&self, psbt: &mut Psbt) -> anyhow::Result<bool> {
-        self.sign_with_options(
-            psbt,
-            SignOptions {
-                trust_witness_utxo: true,
-                ..default()
-            },
-        )
+impl Wallet for MnemonicWallet {
+    fn pay_addr(&self) -> Address {
+        self.peek_addr(self.pay_index)
     }
 
-    pub(crate) fn sign_swap(&self, psbt: &mut Psbt) -> anyhow::Result<bool> {
-        Ok(self.ordi_wallet.sign(
-            psbt,
-            SignOptions {
-                trust_witness_utxo: true,
-                allow_all_sighashes: true,
-                ..default()
-            },
-        )?)
+    fn ordi_addr(&self) -> Address {
+        self.ordi_wallet
+            .peek_address(KeychainKind::External, self.ordi_index)
+            .address
     }
-    pub(crate) fn ctx(&self) -> &Secp256k1<All> {
+
+    fn peek_addr(&self, index: u32) -> Address {
+        self.pay_wallet
+            .peek_address(KeychainKind::External, index)
+            .address
+    }
+
+    fn ctx(&self) -> &Secp256k1<All> {
         self.pay_wallet.secp_ctx()
     }
+
+    fn sign(&self, psbt: &mut Psbt) -> anyhow::Result<bool> {
+        self.sign_with_options(psbt, sign_options())
+    }
+
+    fn sign_all(&self, psbt: &mut Psbt) -> anyhow::Result<bool> {
+        self.pay_wallet.sign(psbt, sign_options())?;
+        Ok(self.ordi_wallet.sign(psbt, sign_options())?)
+    }
+
+    fn sign_swap(&self, psbt: &mut Psbt) -> anyhow::Result<bool> {
+        Ok(self.ordi_wallet.sign(psbt, swap_sign_options())?)
+    }
+
+    fn finalize(&self, psbt: &mut Psbt) -> anyhow::Result<bool> {
+        self.pay_wallet.finalize_psbt(psbt, sign_options())?;
+        Ok(self.ordi_wallet.finalize_psbt(psbt, sign_options())?)
+    }
+
+    fn update_psbt(&self, psbt: &mut Psbt) -> anyhow::Result<()> {
+        self.pay_wallet.update_psbt_with_descriptor(psbt)?;
+        self.ordi_wallet.update_psbt_with_descriptor(psbt)?;
+        Ok(())
+    }
+
+    fn pay_addrs(&self) -> Vec<Address> {
+        self.discovered.pay_addrs(self.pay_addr())
+    }
+
+    fn change_addr(&self) -> Address {
+        self.discovered
+            .change
+            .clone()
+            .unwrap_or_else(|| self.pay_addr())
+    }
+
+    fn change_addrs(&self) -> Vec<Address> {
+        self.discovered.change_addrs()
+    }
+
+    fn discover(&mut self, btc_api: &esplora::Client, gap_limit: u32) -> anyhow::Result<()> {
+        self.discovered =
+            Discovered::by_esplora(&mut self.pay_wallet, self.has_change, btc_api, gap_limit)?;
+        Ok(())
+    }
 }
 
-pub trait Wallet {
-    fn pay_addr(&mut self) -> Address;
-    fn ordi_addr(&mut self) -> Address;
-    fn ctx(&self) -> &Secp256k1<All>;
-    fn sign(&self, psbt: &mut Psbt) -> anyhow::Result<bool>;
+/// Print the first `number` accounts of every preset to find the one of a browser wallet
+pub fn derive(settings: Settings, number: u32) -> anyhow::Result<()> {
+    let Some(mnemonic) = settings.mnemonic.as_deref().filter(|e| !e.is_empty()) else {
+        bail!("Please setting [mnemonic]")
+    };
+    let mut table = comfy_table::Table::new();
+    table.set_header(vec!["Preset", "Account", "Pay", "Ordi"]);
+    for mode in Mode::ALL {
+        for account in 0..number {
+            let wallet = MnemonicWallet::new(mnemonic, mode, account, settings.network)?;
+            table.add_row(vec![
+                format!("{:?}", mode),
+                account.to_string(),
+                wallet.pay_addr().to_string(),
+                wallet.ordi_addr().to_string(),
+            ]);
+        }
+    }
+    println!("{}", table);
+    Ok(())
+}
+
+/// Single WIF key, pay and ordi share its p2tr address
+pub(crate) struct PrivateKeyWallet {
+    wallet: bdk::Wallet,
+}
+
+impl PrivateKeyWallet {
+    pub(crate) fn new(wif: &str, network: Network) -> anyhow::Result<Self> {
+        let key = PrivateKey::from_wif(wif)?;
+        // testnet keys are shared by signet and regtest
+        if (key.network == Network::Bitcoin) != (network == Network::Bitcoin) {
+            bail!("Private key is not for {}", network);
+        }
+        Ok(Self {
+            wallet: bdk::Wallet::new_no_persist(&format!("tr({})", wif), None, network)?,
+        })
+    }
+}
+
+impl Wallet for PrivateKeyWallet {
+    fn pay_addr(&self) -> Address {
+        self.wallet.peek_address(KeychainKind::External, 0).address
+    }
+
+    fn ordi_addr(&self) -> Address {
+        self.pay_addr()
+    }
+
+    fn peek_addr(&self, _index: u32) -> Address {
+        self.pay_addr()
+    }
+
+    fn ctx(&self) -> &Secp256k1<All> {
+        self.wallet.secp_ctx()
+    }
+
+    fn sign(&self, psbt: &mut Psbt) -> anyhow::Result<bool> {
+        Ok(self.wallet.sign(psbt, sign_options())?)
+    }
+
+    fn sign_all(&self, psbt: &mut Psbt) -> anyhow::Result<bool> {
+        self.sign(psbt)
+    }
+
+    fn sign_swap(&self, psbt: &mut Psbt) -> anyhow::Result<bool> {
+        Ok(self.wallet.sign(psbt, swap_sign_options())?)
+    }
+
+    fn finalize(&self, psbt: &mut Psbt) -> anyhow::Result<bool> {
+        Ok(self.wallet.finalize_psbt(psbt, sign_options())?)
+    }
+}
+
+/// Pay and ordi descriptors, watch-only if they hold no private keys
+pub(crate) struct DescriptorWallet {
+    pay_wallet: bdk::Wallet,
+    ordi_wallet: bdk::Wallet,
+    has_change: bool,
+    discovered: Discovered,
+}
+
+impl DescriptorWallet {
+    /// Ordi falls back to the pay descriptor
+    pub(crate) fn new(
+        pay_descriptor: &str,
+        ordi_descriptor: Option<&str>,
+        network: Network,
+    ) -> anyhow::Result<Self> {
+        let change = change_descriptor(pay_descriptor);
+        Ok(Self {
+            pay_wallet: bdk::Wallet::new_no_persist(pay_descriptor, change.as_deref(), network)?,
+            ordi_wallet: bdk::Wallet::new_no_persist(
+                ordi_descriptor.unwrap_or(pay_descriptor),
+                None,
+                network,
+            )?,
+            has_change: change.is_some(),
+            discovered: Discovered::default(),
+        })
+    }
+
+    fn signing_wallets(&self) -> anyhow::Result<[&bdk::Wallet; 2]> {
+        if self.is_watch_only() {
+            bail!("Watch-only wallet can not sign");
+        }
+        Ok([&self.pay_wallet, &self.ordi_wallet])
+    }
+}
+
+impl Wallet for DescriptorWallet {
+    fn pay_addr(&self) -> Address {
+        self.peek_addr(0)
+    }
+
+    fn ordi_addr(&self) -> Address {
+        self.ordi_wallet
+            .peek_address(KeychainKind::External, 0)
+            .address
+    }
+
+    fn peek_addr(&self, index: u32) -> Address {
+        self.pay_wallet
+            .peek_address(KeychainKind::External, index)
+            .address
+    }
+
+    fn ctx(&self) -> &Secp256k1<All> {
+        self.pay_wallet.secp_ctx()
+    }
+
+    fn sign(&self, psbt: &mut Psbt) -> anyhow::Result<bool> {
+        let [pay, _] = self.signing_wallets()?;
+        Ok(pay.sign(psbt, sign_options())?)
+    }
+
+    fn sign_all(&self, psbt: &mut Psbt) -> anyhow::Result<bool> {
+        let [pay, ordi] = self.signing_wallets()?;
+        pay.sign(psbt, sign_options())?;
+        Ok(ordi.sign(psbt, sign_options())?)
+    }
+
+    fn sign_swap(&self, psbt: &mut Psbt) -> anyhow::Result<bool> {
+        let [_, ordi] = self.signing_wallets()?;
+        Ok(ordi.sign(psbt, swap_sign_options())?)
+    }
+
+    fn finalize(&self, psbt: &mut Psbt) -> anyhow::Result<bool> {
+        self.pay_wallet.finalize_psbt(psbt, sign_options())?;
+        Ok(self.ordi_wallet.finalize_psbt(psbt, sign_options())?)
+    }
+
+    fn update_psbt(&self, psbt: &mut Psbt) -> anyhow::Result<()> {
+        self.pay_wallet.update_psbt_with_descriptor(psbt)?;
+        self.ordi_wallet.update_psbt_with_descriptor(psbt)?;
+        Ok(())
+    }
+
+    fn pay_addrs(&self) -> Vec<Address> {
+        self.discovered.pay_addrs(self.pay_addr())
+    }
+
+    fn change_addr(&self) -> Address {
+        self.discovered
+            .change
+            .clone()
+            .unwrap_or_else(|| self.pay_addr())
+    }
+
+    fn change_addrs(&self) -> Vec<Address> {
+        self.discovered.change_addrs()
+    }
+
+    fn discover(&mut self, btc_api: &esplora::Client, gap_limit: u32) -> anyhow::Result<()> {
+        self.discovered =
+            Discovered::by_esplora(&mut self.pay_wallet, self.has_change, btc_api, gap_limit)?;
+        Ok(())
+    }
+
+    fn is_watch_only(&self) -> bool {
+        [&self.pay_wallet, &self.ordi_wallet]
+            .iter()
+            .all(|e| e.get_signers(KeychainKind::External).signers().is_empty())
+    }
 }
 
-//
-// pub(crate) struct PrivateKeyWallet {
-//     wif: String,
-//     pay_addr: String,
-//     secp256k1: Secp256k1<All>,
-// }
-//
-// impl PrivateKeyWallet {
-//     pub(crate) fn new(pk_wif: &str, pay_addr: &str) -> Self {
-//         Self {
-//             wif: pk_wif.to_string(),
-//             pay_addr: pay_addr.to_string(),
-//             secp256k1: Secp256k1::new(),
-//         }
-//     }
-//
-//     pub(crate) fn private_key(&self) -> anyhow::Result<PrivateKey> {
-//         Ok(PrivateKey::from_wif(&self.wif)?)
-//     }
-//
-//     pub(crate) fn public_key(&self) -> anyhow::Result<PublicKey> {
-//         let pk = self.private_key()?;
-//         Ok(pk.public_key(&Secp256k1::new()))
-//     }
-//     pub(crate) fn private_keys(&self) -> anyhow::Result<HashMap<PublicKey, PrivateKey>> {
-//         let mut map = HashMap::new();
-//         let pk = PrivateKey::from_wif(&self.wif)?;
-//         map.insert(pk.public_key(&Secp256k1::new()), pk);
-//         Ok(map)
-//     }
-//
-//     pub(crate) fn sign_psbt(&self, psbt: &mut Psbt) -> anyhow::Result<()> {
-//         let signingkeys = psbt.sign(&self.private_keys()?, &Secp256k1::new()).unwrap();
-//         for (index, signingkey) in signingkeys {
-//             println!("[{index}] : [{signingkey:?}]")
-//         }
-//         Ok(())
-//     }
-// }
-//
-// impl Wallet for PrivateKeyWallet {
-//     fn sign_psbt(&self, psbt: &mut Psbt) -> anyhow::Result<()> {
-//         self.sign_psbt(psbt)
-//     }
-//
-//     fn pay_addr(&self) -> anyhow::Result<Address> {
-//         Ok(Address::from_str(&self.pay_addr)?.assume_checked())
-//     }
-//
-//     fn public_key(&self) -> anyhow::Result<PublicKey> {
-//         self.public_key()
-//     }
-//
-//     fn secret_key(&self) -> anyhow::Result<SecretKey> {
-//         Ok(self.private_key()?.inner)
-//     }
-//
-//     fn xpriv(&self) -> anyhow::Result<Xpriv> {
-//         unreachable!()
-//     }
-//
-//     fn secp(&self) -> &Secp256k1<All> {
-//         &self.secp256k1
-//     }
-// }
-//
-// pub(crate) struct MnemonicWallet {
-//     network: Network,
-//     pub(crate) derivation_path: DerivationPath,
-//     pub(crate) master_xpriv: Xpriv,
-//     pub(crate) master_xpub: Xpub,
-//     pass: Option<String>,
-//     pub(crate) secp: Secp256k1<All>,
-// }
-//
-// impl MnemonicWallet {
-//     pub(crate) fn new(words: &str, network: Network) -> anyhow::Result<Self> {
-//         let secp = Secp256k1::new();
-//         let master_xpriv = Self::master_xpriv(network, words, None)?;
-//         let master_xpub = Xpub::from_priv(&secp, &master_xpriv);
-//         let derivation_path = DerivationPath::from_str("m/86'/0'/0'/0/0")?;
-//         Ok(Self {
-//             network,
-//             master_xpriv,
-//             master_xpub,
-//             pass: None,
-//             secp,
-//             derivation_path,
-//         })
-//     }
-//     pub(crate) fn master_xpriv(
-//         network: Network,
-//         words: &str,
-//         pass: Option<&str>,
-//     ) -> anyhow::Result<Xpriv> {
-//         let mnemonic = Mnemonic::parse(words)?;
-//         let pass = pass.unwrap_or_default();
-//         let seed = mnemonic.to_seed(pass);
-//         let master_key = Xpriv::new_master(network, &seed)?;
-//         Ok(master_key)
-//     }
-//
-//     pub(crate) fn account0_xpriv(&self) -> anyhow::Result<Xpriv> {
-//         let de = self
-//             .master_xpriv
-//             .derive_priv(&self.secp, &self.derivation_path)?;
-//         Ok(de)
-//     }
-//
-//     pub(crate) fn account0_xpub(&self) -> anyhow::Result<Xpub> {
-//         Ok(Xpub::from_priv(&self.secp, &(self.account0_xpriv()?)))
-//     }
-//
-//     pub(crate) fn account0_addr(&self) -> anyhow::Result<Address> {
-//         Ok({
-//             Address::p2tr(
-//                 &self.secp,
-//                 self.account0_xpub()?.public_key.to_x_only_pubkey(),
-//                 None,
-//                 self.network,
-//             )
-//         })
-//     }
-//
-//     pub(crate) fn master_fingerprint(&self) -> Fingerprint {
-//         self.master_xpub.fingerprint()
-//     }
-// }
-
-//
-// impl Wallet for MnemonicWallet {
-//     fn sign_psbt(&self, psbt: &mut Psbt) -> anyhow::Result<()> {
-//         self.sign_psbt(psbt)
-//     }
-//
-//     fn pay_addr(&self) -> anyhow::Result<Address> {
-//         Ok(Address::from_str(&self.pay_addr)?.require_network(self.network)?)
-//     }
-//
-//     fn public_key(&self) -> anyhow::Result<PublicKey> {
-//         unreachable!()
-//     }
-//
-//     fn secret_key(&self) -> anyhow::Result<SecretKey> {
-//         unreachable!()
-//     }
-//
-//     fn xpriv(&self) -> anyhow::Result<Xpriv> {
-//         self.xpriv()
-//     }
-//
-//     fn secp(&self) -> &Secp256k1<All> {
-//         &self.secp256k1
-//     }
-// }
+#[cfg(test)]
+mod tests {
+    use bitcoin::{
+        bip32::{DerivationPath, Xpub},
+        secp256k1::SecretKey,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_private_key_wallet() {
+        let secp = Secp256k1::new();
+        let key = PrivateKey::new(SecretKey::from_slice(&[1; 32]).unwrap(), Network::Bitcoin);
+        let wallet = PrivateKeyWallet::new(&key.to_wif(), Network::Bitcoin).unwrap();
+        let addr = Address::p2tr(
+            &secp,
+            key.inner.x_only_public_key(&secp).0,
+            None,
+            Network::Bitcoin,
+        );
+        assert_eq!(wallet.pay_addr(), addr);
+        assert_eq!(wallet.ordi_addr(), addr);
+        assert!(PrivateKeyWallet::new(&key.to_wif(), Network::Testnet).is_err());
+    }
+
+    #[test]
+    fn test_descriptor_config() {
+        #[derive(Deserialize)]
+        struct Config {
+            pay: DescriptorConfig,
+            ordi: DescriptorConfig,
+        }
+        let config: Config = toml::from_str(
+            r#"
+            pay = { script = "wpkh" }
+            ordi = "tr(xpub/0/*)"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.pay,
+            DescriptorConfig::Derived {
+                script: ScriptType::Wpkh,
+                account: 0,
+                path: None
+            }
+        );
+        assert_eq!(
+            config.ordi,
+            DescriptorConfig::Raw("tr(xpub/0/*)".to_string())
+        );
+
+        // BIP 84/86/49 test vectors
+        let mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let first_addr = |script: ScriptType, network: Network| {
+            let config = DescriptorConfig::Derived {
+                script,
+                account: 0,
+                path: None,
+            };
+            let descriptor = config.descriptor(Some(mnemonic), network).unwrap();
+            DescriptorWallet::new(&descriptor, None, network)
+                .unwrap()
+                .pay_addr()
+                .to_string()
+        };
+        assert_eq!(
+            first_addr(ScriptType::Wpkh, Network::Bitcoin),
+            "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu"
+        );
+        assert_eq!(
+            first_addr(ScriptType::Tr, Network::Bitcoin),
+            "bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr"
+        );
+        assert_eq!(
+            first_addr(ScriptType::ShWpkh, Network::Testnet),
+            "2Mww8dCYPUpKHofjgcXcBCEGmniw9CoaiD2"
+        );
+        assert!(config.pay.descriptor(None, Network::Bitcoin).is_err());
+    }
+
+    #[test]
+    fn test_mode() {
+        let mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let leather = MnemonicWallet::new(mnemonic, Mode::Leather, 0, Network::Bitcoin).unwrap();
+        assert_eq!(
+            leather.pay_addr().to_string(),
+            "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu"
+        );
+        assert_eq!(
+            leather.ordi_addr().to_string(),
+            "bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr"
+        );
+        for mode in [Mode::Okx, Mode::MagicEden] {
+            let wallet = MnemonicWallet::new(mnemonic, mode, 0, Network::Bitcoin).unwrap();
+            assert_eq!(
+                wallet.pay_addr().to_string(),
+                "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu"
+            );
+            assert_eq!(
+                wallet.ordi_addr().to_string(),
+                "bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr"
+            );
+            // the account is the bip32 account, not the address index
+            let next = MnemonicWallet::new(mnemonic, mode, 1, Network::Bitcoin).unwrap();
+            assert_ne!(
+                next.pay_addr(),
+                wallet
+                    .pay_wallet
+                    .peek_address(KeychainKind::External, 1)
+                    .address
+            );
+        }
+        let xverse = MnemonicWallet::new(mnemonic, Mode::XVerse, 0, Network::Testnet).unwrap();
+        assert_eq!(
+            xverse.pay_addr().to_string(),
+            "2Mww8dCYPUpKHofjgcXcBCEGmniw9CoaiD2"
+        );
+        let unisat = MnemonicWallet::new(mnemonic, Mode::Unisat, 1, Network::Bitcoin).unwrap();
+        assert_eq!(unisat.pay_addr(), unisat.ordi_addr());
+        assert_eq!(
+            unisat.pay_addr(),
+            leather
+                .ordi_wallet
+                .peek_address(KeychainKind::External, 1)
+                .address
+        );
+        let peek = MnemonicWallet::new(mnemonic, Mode::Peek, 0, Network::Bitcoin).unwrap();
+        assert_eq!(peek.ordi_addr(), unisat.pay_addr());
+
+        #[derive(Deserialize)]
+        struct Config {
+            wallet_mode: Mode,
+        }
+        let mode = |s: &str| {
+            toml::from_str::<Config>(&format!("wallet_mode = \"{}\"", s))
+                .unwrap()
+                .wallet_mode
+        };
+        assert_eq!(mode("other"), Mode::Unisat);
+        assert_eq!(mode("xverse"), Mode::XVerse);
+        assert_eq!(mode("unisat-segwit"), Mode::UnisatSegwit);
+        assert_eq!(mode("okx"), Mode::Okx);
+        assert_eq!(mode("magic-eden"), Mode::MagicEden);
+    }
+
+    #[test]
+    fn test_change_descriptor() {
+        assert_eq!(
+            change_descriptor("wpkh([73c5da0a/84'/0'/0']xpub/0/*)#abcdefgh").as_deref(),
+            Some("wpkh([73c5da0a/84'/0'/0']xpub/1/*)")
+        );
+        assert_eq!(change_descriptor("tr(xpub/0/0)"), None);
+    }
+
+    #[test]
+    fn test_discover() {
+        let mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let mut wallet = MnemonicWallet::new(mnemonic, Mode::Leather, 0, Network::Bitcoin).unwrap();
+        let addr = |wallet: &MnemonicWallet, keychain, index| {
+            wallet.pay_wallet.peek_address(keychain, index).address
+        };
+        let used = vec![
+            addr(&wallet, KeychainKind::External, 0),
+            addr(&wallet, KeychainKind::External, 2),
+            addr(&wallet, KeychainKind::Internal, 1),
+        ];
+        let mut asked = 0;
+        wallet.discovered = Discovered::scan(&mut wallet.pay_wallet, wallet.has_change, 3, |e| {
+            asked += 1;
+            Ok(used.contains(e))
+        })
+        .unwrap();
+        // external 0..=5, internal 0..=4
+        assert_eq!(asked, 11);
+        assert_eq!(wallet.pay_addrs(), used);
+        assert_eq!(
+            wallet.change_addr(),
+            addr(&wallet, KeychainKind::Internal, 2)
+        );
+        assert_eq!(
+            wallet.change_addrs(),
+            vec![
+                addr(&wallet, KeychainKind::Internal, 1),
+                addr(&wallet, KeychainKind::Internal, 2)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_watch_only_wallet() {
+        let secp = Secp256k1::new();
+        let key = PrivateKey::new(SecretKey::from_slice(&[1; 32]).unwrap(), Network::Bitcoin);
+        let descriptor = format!("tr({})", key.inner.x_only_public_key(&secp).0);
+        let wallet = DescriptorWallet::new(&descriptor, None, Network::Bitcoin).unwrap();
+        assert!(wallet.is_watch_only());
+        assert_eq!(wallet.pay_addr(), wallet.ordi_addr());
+
+        let mut psbt = Psbt::from_unsigned_tx(bitcoin::Transaction {
+            version: bitcoin::transaction::Version::TWO,
+            lock_time: bitcoin::absolute::LockTime::ZERO,
+            input: vec![],
+            output: vec![],
+        })
+        .unwrap();
+        assert!(wallet.sign(&mut psbt).is_err());
+    }
+
+    #[test]
+    fn test_update_psbt() {
+        let secp = Secp256k1::new();
+        let master = Xpriv::new_master(Network::Bitcoin, &[1; 32]).unwrap();
+        let path = DerivationPath::from_str("m/86'/0'/0'").unwrap();
+        let xpub = Xpub::from_priv(&secp, &master.derive_priv(&secp, &path).unwrap());
+        let descriptor = format!("tr([{}/86'/0'/0']{}/0/*)", master.fingerprint(&secp), xpub);
+        let wallet = DescriptorWallet::new(&descriptor, None, Network::Bitcoin).unwrap();
+
+        let mut psbt = Psbt::from_unsigned_tx(bitcoin::Transaction {
+            version: bitcoin::transaction::Version::TWO,
+            lock_time: bitcoin::absolute::LockTime::ZERO,
+            input: vec![bitcoin::TxIn::default()],
+            output: vec![],
+        })
+        .unwrap();
+        psbt.inputs[0].witness_utxo = Some(bitcoin::TxOut {
+            value: bitcoin::Amount::from_sat(10_000),
+            script_pubkey: wallet.pay_addr().script_pubkey(),
+        });
+        wallet.update_psbt(&mut psbt).unwrap();
+
+        let input = &psbt.inputs[0];
+        assert!(input.tap_internal_key.is_some());
+        let (_, (fingerprint, path)) = input.tap_key_origins.values().next().unwrap();
+        assert_eq!(*fingerprint, master.fingerprint(&secp));
+        assert_eq!(*path, DerivationPath::from_str("m/86'/0'/0'/0/0").unwrap());
+    }
+}
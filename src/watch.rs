@@ -0,0 +1,363 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fs,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use anyhow::bail;
+use bitcoin::Amount;
+use serde::Deserialize;
+
+use crate::{
+    bid::BidPolicy,
+    btc_api::ordinal::{self, Ordinal},
+    setting::Settings,
+    snipe::{find_liftable_ordinals, snipe, Type},
+    tracker::TxEvent,
+};
+
+#[derive(Deserialize)]
+struct Rules {
+    rule: Vec<Rule>,
+}
+
+/// One of `addr`, `inscription` or `rune`, and at least one price limit
+#[derive(Deserialize, Debug)]
+struct Rule {
+    /// Listings in mempool txs touching this address
+    addr: Option<String>,
+    /// Listings of this inscription id
+    inscription: Option<String>,
+    /// Listings of this rune in any mempool tx
+    rune: Option<String>,
+    /// Btc, all listings of the tx
+    max_price: Option<f64>,
+    /// Sat per whole rune, rune rules only
+    max_unit_price: Option<f64>,
+}
+
+/// Txids checked by `watch` are forgotten past this many
+const SEEN_CAPACITY: usize = 100_000;
+
+/// Watch the mempool for listings matching `rules` and snipe them without confirm. Txs come
+/// from the tx tracker, rune rules check every mempool tx, address and inscription rules the
+/// txs of their address. The owner of each inscription is looked up again every `interval`
+/// seconds.
+pub fn watch(
+    settings: Settings,
+    rules: &Path,
+    increase_rate: u64,
+    interval: u64,
+    broadcast: bool,
+) -> anyhow::Result<()> {
+    let rules = read_rules(rules)?;
+    let btc_api = settings.btc_api();
+    let ordi_api = settings.ordi_api();
+    let wallet = settings.wallet()?;
+    let mut seen = Seen::new(SEEN_CAPACITY);
+
+    let rune_rules = rules
+        .iter()
+        .enumerate()
+        .filter(|(_, e)| e.rune.is_some())
+        .map(|(i, _)| i)
+        .collect::<Vec<_>>();
+    let mut tracker = settings.tx_tracker()?;
+    if !rune_rules.is_empty() {
+        tracker.track_mempool()?;
+    }
+    // rule index -> address, inscription rules are filled by the lookup
+    let mut addrs = HashMap::new();
+    for (i, rule) in rules.iter().enumerate() {
+        if let Some(addr) = &rule.addr {
+            tracker.track_address(addr)?;
+            addrs.insert(i, addr.clone());
+        }
+    }
+
+    let mut check = |candidates: HashMap<String, Vec<usize>>| {
+        for (txid, rule_indexes) in candidates {
+            if !seen.insert(&txid) {
+                continue;
+            }
+            let listings = btc_api.get_transaction(&txid).and_then(|pool_tx| {
+                let tx = btc_api.get_btc_transaction(&txid)?;
+                let listings = find_liftable_ordinals(&ordi_api, &pool_tx, &tx)?
+                    .into_iter()
+                    .map(|(_, ordinal, _, txout)| (ordinal, txout.value))
+                    .collect::<Vec<_>>();
+                let units = rune_units(&ordi_api, &listings)?;
+                Ok((listings, units))
+            });
+            let (listings, units) = match listings {
+                Ok(e) => e,
+                Err(e) => {
+                    log::debug!("[watch] {} skipped: {}", txid, e);
+                    continue;
+                }
+            };
+            let Some(rule) = rule_indexes
+                .into_iter()
+                .map(|i| &rules[i])
+                .find(|e| e.matches(&listings, units))
+            else {
+                log::info!("[watch] {} does not match any rule", txid);
+                continue;
+            };
+            log::info!("[watch] {} matches {:?}", txid, rule);
+            if let Err(e) = snipe(
+                settings.clone(),
+                wallet.as_ref(),
+                &txid,
+                "",
+                Type::Auto,
+                BidPolicy::once(increase_rate),
+                broadcast,
+                false,
+                true,
+                false,
+                false,
+                None,
+                None,
+                None,
+                false,
+            ) {
+                log::error!("[watch] Snipe {} failed: {}", txid, e);
+            }
+        }
+    };
+
+    loop {
+        for (i, rule) in rules.iter().enumerate() {
+            let Some(id) = &rule.inscription else {
+                continue;
+            };
+            match ordi_api.fetch_inscription(id) {
+                Ok(e) => match e.address {
+                    Some(addr) => {
+                        tracker.track_address(&addr)?;
+                        addrs.insert(i, addr);
+                    }
+                    None => {
+                        addrs.remove(&i);
+                    }
+                },
+                Err(e) => log::warn!("[watch] Rule {} lookup failed: {}", i, e),
+            }
+        }
+
+        // tracker events until the next lookup
+        let next_lookup = Instant::now() + Duration::from_secs(interval);
+        loop {
+            let left = next_lookup.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                break;
+            }
+            match tracker.next_event(left)? {
+                Some(TxEvent::MempoolTx { txid }) => {
+                    check(HashMap::from([(txid, rune_rules.clone())]));
+                }
+                Some(TxEvent::AddressTx { address, txid }) => {
+                    let rule_indexes = addrs
+                        .iter()
+                        .filter(|(_, e)| **e == address)
+                        .map(|(i, _)| *i)
+                        .collect::<Vec<_>>();
+                    if !rule_indexes.is_empty() {
+                        check(HashMap::from([(txid, rule_indexes)]));
+                    }
+                }
+                Some(_) => {}
+                None => break,
+            }
+        }
+    }
+}
+
+/// Txids already checked, the oldest are forgotten past `capacity`
+struct Seen {
+    txids: HashSet<String>,
+    order: VecDeque<String>,
+    capacity: usize,
+}
+
+impl Seen {
+    fn new(capacity: usize) -> Self {
+        Self {
+            txids: HashSet::new(),
+            order: VecDeque::new(),
+            capacity,
+        }
+    }
+
+    /// False if `txid` was seen
+    fn insert(&mut self, txid: &str) -> bool {
+        if !self.txids.insert(txid.to_string()) {
+            return false;
+        }
+        self.order.push_back(txid.to_string());
+        if self.order.len() > self.capacity {
+            let oldest = self.order.pop_front().unwrap();
+            self.txids.remove(&oldest);
+        }
+        true
+    }
+}
+
+fn read_rules(path: &Path) -> anyhow::Result<Vec<Rule>> {
+    let rules = toml::from_str::<Rules>(&fs::read_to_string(path)?)?.rule;
+    if rules.is_empty() {
+        bail!("No rule in {}", path.display());
+    }
+    for (i, rule) in rules.iter().enumerate() {
+        let targets = [
+            rule.addr.is_some(),
+            rule.inscription.is_some(),
+            rule.rune.is_some(),
+        ];
+        if targets.into_iter().filter(|e| *e).count() != 1 {
+            bail!("Rule {}: set one of addr, inscription or rune", i);
+        }
+        if rule.max_price.is_none() && rule.max_unit_price.is_none() {
+            bail!("Rule {}: set max_price or max_unit_price", i);
+        }
+        if rule.max_unit_price.is_some() && rule.rune.is_none() {
+            bail!("Rule {}: max_unit_price is for rune rules", i);
+        }
+    }
+    Ok(rules)
+}
+
+/// Whole runes of the rune listings, the raw amount of each output over the divisibility of
+/// its rune
+fn rune_units(ordi_api: &ordinal::Client, listings: &[(Ordinal, Amount)]) -> anyhow::Result<f64> {
+    let mut units = 0.0;
+    for (ordinal, _) in listings {
+        if let Ordinal::Rune {
+            name, out_point, ..
+        } = ordinal
+        {
+            let entry = ordi_api.fetch_rune_id(name)?.entry;
+            let amount = ordi_api.output(out_point)?.rune_amount(&entry.spaced_rune);
+            units += whole_units(amount, entry.divisibility);
+        }
+    }
+    Ok(units)
+}
+
+fn whole_units(amount: u128, divisibility: u32) -> f64 {
+    amount as f64 / 10f64.powi(divisibility as i32)
+}
+
+impl Rule {
+    /// `listings` are the liftable (ordinal, price) of one tx, all of them are sniped. `units`
+    /// are the whole runes of the listings, see `rune_units`.
+    fn matches(&self, listings: &[(Ordinal, Amount)], units: f64) -> bool {
+        if listings.is_empty() {
+            return false;
+        }
+        let total = listings.iter().map(|(_, price)| *price).sum::<Amount>();
+        let target = if let Some(id) = &self.inscription {
+            listings
+                .iter()
+                .any(|(o, _)| matches!(o, Ordinal::Inscription { id: e, .. } if e == id))
+        } else if let Some(rune) = &self.rune {
+            listings.iter().all(
+                |(o, _)| matches!(o, Ordinal::Rune { name, .. } if unspaced(name) == unspaced(rune)),
+            )
+        } else {
+            true
+        };
+        target
+            && self.max_price.map_or(true, |e| total.to_btc() <= e)
+            && self
+                .max_unit_price
+                .map_or(true, |e| units > 0.0 && total.to_sat() as f64 / units <= e)
+    }
+}
+
+fn unspaced(name: &str) -> String {
+    name.chars().filter(|e| *e != '•' && *e != '.').collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use bitcoin::OutPoint;
+    use ordinals::RuneId;
+
+    use super::*;
+
+    fn rule(content: &str) -> Rule {
+        toml::from_str::<Rules>(content)
+            .unwrap()
+            .rule
+            .pop()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_rule_matches() {
+        let inscription = (
+            Ordinal::Inscription {
+                id: "a".to_string(),
+                value: Amount::from_sat(546),
+                out_point: OutPoint::null(),
+            },
+            Amount::from_sat(100_000),
+        );
+        let rune = (
+            Ordinal::Rune {
+                id: RuneId::from_str("840000:1").unwrap(),
+                name: "HELLO•WORLD".to_string(),
+                value: Amount::from_sat(546),
+                number: 1000,
+                div: 0,
+                out_point: OutPoint::null(),
+            },
+            Amount::from_sat(100_000),
+        );
+
+        let addr = rule("[[rule]]\naddr = \"bc1\"\nmax_price = 0.001");
+        assert!(addr.matches(&[inscription.clone()], 0.0));
+        assert!(!addr.matches(&[inscription.clone(), rune.clone()], 1000.0));
+        assert!(!addr.matches(&[], 0.0));
+
+        let id = rule("[[rule]]\ninscription = \"a\"\nmax_price = 0.0005");
+        assert!(!id.matches(&[inscription.clone()], 0.0));
+
+        let by_unit = rule("[[rule]]\nrune = \"HELLOWORLD\"\nmax_unit_price = 100");
+        assert!(by_unit.matches(&[rune.clone()], 1000.0));
+        assert!(!by_unit.matches(&[rune.clone(), inscription], 1000.0));
+        let by_unit = rule("[[rule]]\nrune = \"HELLOWORLD\"\nmax_unit_price = 99.9");
+        assert!(!by_unit.matches(&[rune.clone()], 1000.0));
+
+        // 100_000 raw with divisibility 2 is 1000 runes, 100 sat each
+        let by_unit = rule("[[rule]]\nrune = \"HELLOWORLD\"\nmax_unit_price = 100");
+        assert!(by_unit.matches(&[rune.clone()], whole_units(100_000, 2)));
+        // not 1 sat per rune
+        let by_unit = rule("[[rule]]\nrune = \"HELLOWORLD\"\nmax_unit_price = 1");
+        assert!(!by_unit.matches(&[rune], whole_units(100_000, 2)));
+    }
+
+    #[test]
+    fn test_whole_units() {
+        assert_eq!(whole_units(150, 2), 1.5);
+        assert_eq!(whole_units(1000, 0), 1000.0);
+        assert_eq!(whole_units(10_000_000_000, 5), 100_000.0);
+    }
+
+    #[test]
+    fn test_seen() {
+        let mut seen = Seen::new(2);
+        assert!(seen.insert("a"));
+        assert!(!seen.insert("a"));
+        assert!(seen.insert("b"));
+        assert!(seen.insert("c"));
+        // "a" is forgotten
+        assert!(seen.insert("a"));
+        assert!(!seen.insert("c"));
+    }
+}